@@ -22,33 +22,296 @@
  */
 
 use ansi_term::Color;
-use calculator_engine::execution::hybrid::{Hybrid, JitOptimizationLevel};
+use calculator_engine::ast::{AstBuilder, AstKind, Program};
+use calculator_engine::diagnostics::{render, render_in_file, ColorMode};
+use calculator_engine::execution::hybrid::{Environment, Hybrid, JitOptimizationLevel};
+use calculator_engine::format::{Notation, MAX_DIGITS, MAX_EXACT_DIGITS};
+use calculator_engine::number::{
+    Complex, Context, Decimal, Integer, Interval, Number, Quantity, Rational, MAX_PRECISION,
+};
+use calculator_engine::syntax::Syntax;
+use calculator_engine::Error;
+use std::iter::Peekable;
+
+/// Number type, which expression is evaluated over.
+enum Mode {
+    Float,
+    Exact,
+    Decimal,
+    Complex,
+    Interval,
+    Integer,
+    Units,
+}
+
+/// Options, which precede the expression.
+struct Options {
+    mode: Mode,
+    /// Notation of results, shortest prints floats in the shortest form and rationals as fractions.
+    notation: Notation,
+    /// Precision and rounding of decimal mode.
+    context: Context,
+    /// Radix of integer results in float and integer modes.
+    base: u32,
+    /// Print result of every statement, not only of the last one.
+    all: bool,
+    syntax: Syntax,
+    /// Print how the source is grouped instead of evaluating it.
+    explain: bool,
+}
+
+impl Options {
+    fn parse(args: &mut Peekable<impl Iterator<Item = String>>) -> Result<Options, String> {
+        let mut options = Options {
+            mode: Mode::Float,
+            notation: Notation::Shortest,
+            context: Context::default(),
+            base: 10,
+            all: false,
+            syntax: Syntax::default(),
+            explain: false,
+        };
+
+        while let Some(arg) = args.peek() {
+            match arg.as_str() {
+                "--exact" => options.mode = Mode::Exact,
+                "--decimal" => options.mode = Mode::Decimal,
+                "--complex" => {
+                    options.mode = Mode::Complex;
+                    options.syntax.imaginary = true;
+                }
+                "--interval" => options.mode = Mode::Interval,
+                "--integer" => options.mode = Mode::Integer,
+                "--units" => {
+                    options.mode = Mode::Units;
+                    options.syntax.units = true;
+                }
+                "--all" => options.all = true,
+                "--explain" => options.explain = true,
+                "--locale" => {
+                    args.next();
+                    options.syntax.locale = args
+                        .peek()
+                        .ok_or("--locale expects c, en, de, fr or ch")?
+                        .parse()?;
+                }
+                "--implicit" => {
+                    args.next();
+                    options.syntax.implicit_multiplication = args
+                        .peek()
+                        .ok_or("--implicit expects same or tight")?
+                        .parse()?;
+                }
+                "--digits" => {
+                    args.next();
+                    options.notation = Notation::Fixed(
+                        args.peek()
+                            .and_then(|digits| digits.parse().ok())
+                            .ok_or("--digits expects number of digits")?,
+                    );
+                }
+                "--notation" => {
+                    args.next();
+                    options.notation = args
+                        .peek()
+                        .ok_or("--notation expects shortest, fixed:N, significant:N, scientific, engineering or si")?
+                        .parse()?;
+                }
+                "--precision" => {
+                    args.next();
+                    options.context.precision = args
+                        .peek()
+                        .and_then(|precision| precision.parse().ok())
+                        .filter(|precision| *precision > 0 && *precision <= MAX_PRECISION)
+                        .ok_or("--precision expects number of digits from 1 to 10000")?;
+                }
+                "--base" => {
+                    args.next();
+                    options.base = args
+                        .peek()
+                        .and_then(|base| base.parse().ok())
+                        .filter(|base| [2, 8, 10, 16].contains(base))
+                        .ok_or("--base expects 2, 8, 10 or 16")?;
+                }
+                "--rounding" => {
+                    args.next();
+                    options.context.rounding = args
+                        .peek()
+                        .ok_or("--rounding expects half-even, half-up or toward-zero")?
+                        .parse()?;
+                }
+                _ => break,
+            }
+            args.next();
+        }
+
+        // Only exact and decimal results have more digits than `f64`
+        let max_digits = match options.mode {
+            Mode::Exact | Mode::Decimal => MAX_EXACT_DIGITS,
+            _ => MAX_DIGITS,
+        };
+        options.notation.check_digits(max_digits)?;
+
+        Ok(options)
+    }
+}
 
 fn main() {
-    let red = Color::Red.bold();
     let green = Color::Green;
+    let red = Color::Red.bold();
+    let mode = if std::env::var_os("NO_COLOR").is_some() {
+        ColorMode::Plain
+    } else {
+        ColorMode::Ansi
+    };
+
+    let mut args = std::env::args().skip(1).peekable();
+    let options = match Options::parse(&mut args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", red.paint(message));
+            std::process::exit(2);
+        }
+    };
+    let (source, file) = match args.peek().map(String::as_str) {
+        Some("run") => {
+            args.next();
+            let file = match (args.next(), args.next()) {
+                (Some(file), None) => file,
+                _ => {
+                    eprintln!("{}", red.paint("run expects a single file"));
+                    std::process::exit(2);
+                }
+            };
+            match std::fs::read_to_string(&file) {
+                Ok(source) => (source, Some(file)),
+                Err(error) => {
+                    eprintln!("{}", red.paint(format!("Can't read {}: {}", file, error)));
+                    std::process::exit(2);
+                }
+            }
+        }
+        _ => (args.collect::<Vec<_>>().join(" "), None),
+    };
+    let report = |error: &Error| match &file {
+        Some(file) => render_in_file(&source, file, error, mode),
+        None => render(&source, error, mode),
+    };
+
+    // Files report all syntax errors, while expression in arguments reports the first one
+    let (program, mut errors) = options
+        .syntax
+        .scope(|| AstBuilder::build_program_recovering(&source));
+    if file.is_none() {
+        errors.truncate(1);
+    }
+    if !errors.is_empty() {
+        for error in &errors {
+            print!("{}", report(error));
+        }
+        if file.is_some() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if options.explain {
+        println!("{:#}", program);
+        return;
+    }
+
+    let is_expression: Vec<bool> = program
+        .statements
+        .iter()
+        .map(|statement| match statement.kind {
+            AstKind::Assignment { .. } | AstKind::FunctionDefinition { .. } => false,
+            _ => true,
+        })
+        .collect();
+
+    let hybrid = Hybrid::new(JitOptimizationLevel::None);
+    let notation = options.notation;
+    let base = options.base;
+    let (mut results, error) = match options.mode {
+        Mode::Float => execute::<f64>(&hybrid, program, |result| match Integer::from_f64(result) {
+            Ok(integer) if base != 10 => integer.to_radix(base),
+            _ => result.to_notation(notation),
+        }),
+        Mode::Exact => execute::<Rational>(&hybrid, program, |result| result.to_notation(notation)),
+        Mode::Decimal => options
+            .context
+            .scope(|| execute::<Decimal>(&hybrid, program, |result| result.to_notation(notation))),
+        Mode::Complex => {
+            execute::<Complex>(&hybrid, program, |result| result.to_notation(notation))
+        }
+        Mode::Interval => {
+            execute::<Interval>(&hybrid, program, |result| result.to_notation(notation))
+        }
+        Mode::Integer => execute::<Integer>(&hybrid, program, |result| match base {
+            10 => result.to_notation(notation),
+            base => result.to_radix(base),
+        }),
+        Mode::Units => execute::<Quantity>(&hybrid, program, |result| result.to_notation(notation)),
+    };
+
+    // Files print every expression statement, while arguments print only the last result
+    let printed: Vec<String> = if file.is_some() {
+        results
+            .into_iter()
+            .zip(is_expression)
+            .filter_map(|(result, is_expression)| result.filter(|_| is_expression))
+            .collect()
+    } else if options.all {
+        results.into_iter().flatten().collect()
+    } else if error.is_none() {
+        results
+            .pop()
+            .and_then(|result| result)
+            .into_iter()
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-    match Hybrid::exec(
-        &std::env::args()
-            .skip(1)
-            .fold(String::new(), |mut acc, arg| {
-                acc.push(' ');
-                acc.push_str(&arg);
-                acc
-            }),
-        JitOptimizationLevel::None,
-    ) {
-        Ok(result) => println!(
+    // Results in other bases have letters as digits, so they aren't localized
+    let locale = options.syntax.locale;
+    for result in printed {
+        println!(
             "{prefix}{text}{suffix}",
             prefix = green.prefix(),
-            text = result,
+            text = if base == 10 {
+                locale.format(&result)
+            } else {
+                result
+            },
             suffix = green.suffix()
-        ),
-        Err(result) => println!(
-            "{prefix}{text}{suffix}",
-            prefix = red.prefix(),
-            text = result,
-            suffix = red.suffix()
-        ),
-    };
+        );
+    }
+
+    if let Some(error) = error {
+        print!("{}", report(&error));
+        if file.is_some() {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Executes statements one by one, returning formatted results of the executed ones
+/// together with the error, which stopped execution.
+fn execute<N: Number>(
+    hybrid: &Hybrid,
+    program: Program,
+    format: impl Fn(N) -> String,
+) -> (Vec<Option<String>>, Option<Error>) {
+    let mut environment = Environment::<N>::default();
+    let mut results = Vec::new();
+
+    for statement in program.statements {
+        match hybrid.exec_ast(statement, &mut environment) {
+            Ok(result) => results.push(result.map(&format)),
+            Err(error) => return (results, Some(error)),
+        }
+    }
+
+    (results, None)
 }
@@ -21,186 +21,1170 @@
  *
  */
 
+use super::constants;
 use super::errors::Error;
-use super::parser::{parse, Operator, Token};
+use super::functions::Function;
+use super::number::Literal;
+use super::parser::{parse_recovering, Operator, ParseError, ParseUserError, Token};
+use super::span::{Span, Spanned};
+use super::syntax::Syntax;
+use super::units::Unit;
+use itertools::Itertools;
 use log::*;
-use snafu::{OptionExt, Snafu};
+use snafu::Snafu;
 use std::iter::Peekable;
 use std::vec::IntoIter as VecIter;
 use std::{fmt, fmt::Formatter};
 
+/// Node of the tree together with the part of source it was built from.
 #[derive(Clone, Debug)]
-pub enum Ast {
-    Number(f64),
+pub struct Ast {
+    pub kind: AstKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum AstKind {
+    Number(Literal),
+    /// Number with unit, e.g. `3 km`.
+    Quantity {
+        value: Literal,
+        unit: Unit,
+    },
+    /// Conversion `child in unit`.
+    Conversion {
+        child: Box<Ast>,
+        unit: Unit,
+    },
     BinaryOperator {
         operator: Operator,
         left: Box<Ast>,
         right: Box<Ast>,
+        /// Multiplication without operator, e.g. `2x`.
+        implicit: bool,
     },
     UnaryOperator {
         operator: Operator,
         child: Box<Ast>,
     },
+    /// Operator, which follows its operand, e.g. `5!`.
+    PostfixOperator {
+        operator: Operator,
+        child: Box<Ast>,
+    },
     Parenthesis {
         child: Box<Ast>,
     },
+    /// Percentage `child%`, which is hundredth of the child.
+    Percent {
+        child: Box<Ast>,
+    },
+    /// Value increased or decreased by percentage of it, e.g. `200 + 10%`.
+    PercentChange {
+        operator: Operator,
+        base: Box<Ast>,
+        percent: Box<Ast>,
+    },
+    /// Percentage of value, e.g. `50% of 80`.
+    PercentOf {
+        percent: Box<Ast>,
+        whole: Box<Ast>,
+    },
+    /// Conditional expression `if condition then then else otherwise` or
+    /// `condition ? then : otherwise`, which evaluates only one of the branches.
+    Conditional {
+        condition: Box<Ast>,
+        then: Box<Ast>,
+        otherwise: Box<Ast>,
+    },
+    /// Interval literal `[lower, upper]`.
+    Interval {
+        lower: Box<Ast>,
+        upper: Box<Ast>,
+    },
+    FunctionCall {
+        function: Function,
+        arguments: Vec<Ast>,
+    },
+    UserFunctionCall {
+        name: String,
+        arguments: Vec<Ast>,
+    },
+    Variable(String),
+    /// Statement `name = value`, which can only appear at the top of AST.
+    Assignment {
+        name: String,
+        value: Box<Ast>,
+    },
+    /// Statement `name(parameters...) = body`, which can only appear at the top of AST.
+    FunctionDefinition {
+        name: String,
+        parameters: Vec<String>,
+        body: Box<Ast>,
+    },
+    /// Part of the source, which couldn't be parsed.
+    Error,
+}
+
+impl Ast {
+    pub fn new(kind: AstKind, span: Span) -> Ast {
+        Ast { kind, span }
+    }
+
+    /// Visits every node of the tree in pre-order.
+    pub fn visit(&self, visitor: &mut impl FnMut(&Ast)) {
+        visitor(self);
+
+        match &self.kind {
+            AstKind::Number(_)
+            | AstKind::Quantity { .. }
+            | AstKind::Variable(_)
+            | AstKind::Error => {}
+            AstKind::BinaryOperator { left, right, .. }
+            | AstKind::Interval {
+                lower: left,
+                upper: right,
+            }
+            | AstKind::PercentChange {
+                base: left,
+                percent: right,
+                ..
+            }
+            | AstKind::PercentOf {
+                percent: left,
+                whole: right,
+            } => {
+                left.visit(visitor);
+                right.visit(visitor);
+            }
+            AstKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                condition.visit(visitor);
+                then.visit(visitor);
+                otherwise.visit(visitor);
+            }
+            AstKind::UnaryOperator { child, .. }
+            | AstKind::PostfixOperator { child, .. }
+            | AstKind::Parenthesis { child }
+            | AstKind::Percent { child }
+            | AstKind::Conversion { child, .. } => child.visit(visitor),
+            AstKind::FunctionCall { arguments, .. }
+            | AstKind::UserFunctionCall { arguments, .. } => {
+                for argument in arguments {
+                    argument.visit(visitor);
+                }
+            }
+            AstKind::Assignment { value: child, .. }
+            | AstKind::FunctionDefinition { body: child, .. } => child.visit(visitor),
+        }
+    }
+
+    /// Names of user functions, which are called in the tree.
+    pub fn user_function_calls(&self) -> Vec<String> {
+        let mut calls = Vec::new();
+
+        self.visit(&mut |ast| {
+            if let AstKind::UserFunctionCall { name, .. } = &ast.kind {
+                if !calls.contains(name) {
+                    calls.push(name.clone());
+                }
+            }
+        });
+
+        calls
+    }
 }
 
+/// Alternate form `{:#}` writes implicit multiplication explicitly and puts compound operands
+/// in parenthesis, showing how the expression is grouped.
 impl std::fmt::Display for Ast {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        fn display(this: &Ast) -> String {
-            match this {
-                Ast::Number(n) => format!("{}", n),
-                Ast::BinaryOperator {
+        fn display(this: &Ast, explicit: bool) -> String {
+            let child = |ast: &Ast| display(ast, explicit);
+            let operand = |ast: &Ast| match ast.kind {
+                AstKind::BinaryOperator { .. }
+                | AstKind::PercentChange { .. }
+                | AstKind::PercentOf { .. }
+                | AstKind::Conditional { .. }
+                | AstKind::Conversion { .. }
+                    if explicit =>
+                {
+                    format!("({})", child(ast))
+                }
+                _ => child(ast),
+            };
+            let arguments = |arguments: &[Ast]| arguments.iter().map(child).join(", ");
+
+            match &this.kind {
+                AstKind::Number(n) => format!("{}", n),
+                AstKind::Quantity { value, unit } => format!("{} {}", value, unit),
+                AstKind::Conversion { child, unit } => format!("{} in {}", operand(child), unit),
+                AstKind::BinaryOperator {
+                    left,
+                    right,
+                    implicit: true,
+                    ..
+                } if !explicit => format!("{} {}", child(left), child(right)),
+                AstKind::BinaryOperator {
                     left,
                     right,
                     operator,
-                } => format!("{} {} {}", left, operator, right),
-                Ast::UnaryOperator { child, operator } => format!("{}{}", operator, child),
-                Ast::Parenthesis { child } => format!("( {} )", child),
+                    ..
+                } => format!("{} {} {}", operand(left), operator, operand(right)),
+                AstKind::UnaryOperator {
+                    child,
+                    operator: Operator::Not,
+                } => format!("not {}", operand(child)),
+                AstKind::UnaryOperator { child, operator } => {
+                    format!("{}{}", operator, operand(child))
+                }
+                AstKind::PostfixOperator { child, operator } => {
+                    format!("{}{}", operand(child), operator)
+                }
+                AstKind::Parenthesis { child: inner } => format!("( {} )", child(inner)),
+                AstKind::Percent { child } => format!("{}%", operand(child)),
+                AstKind::PercentChange {
+                    operator,
+                    base,
+                    percent,
+                } => format!("{} {} {}%", operand(base), operator, operand(percent)),
+                AstKind::PercentOf { percent, whole } => {
+                    format!("{}% of {}", operand(percent), operand(whole))
+                }
+                AstKind::Conditional {
+                    condition,
+                    then,
+                    otherwise,
+                } => format!(
+                    "if {} then {} else {}",
+                    child(condition),
+                    child(then),
+                    child(otherwise)
+                ),
+                AstKind::Interval { lower, upper } => {
+                    format!("[{}, {}]", child(lower), child(upper))
+                }
+                AstKind::FunctionCall {
+                    function,
+                    arguments: values,
+                } => format!("{}({})", function, arguments(values)),
+                AstKind::UserFunctionCall {
+                    name,
+                    arguments: values,
+                } => format!("{}({})", name, arguments(values)),
+                AstKind::Variable(name) => name.clone(),
+                AstKind::Assignment { name, value } => format!("{} = {}", name, child(value)),
+                AstKind::FunctionDefinition {
+                    name,
+                    parameters,
+                    body,
+                } => format!("{}({}) = {}", name, parameters.join(", "), child(body)),
+                AstKind::Error => "<error>".to_owned(),
             }
         }
 
-        write!(f, "{}", display(self))
+        write!(f, "{}", display(self, f.alternate()))
+    }
+}
+
+/// Statements, which are separated by `;` or new lines and executed one by one.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub statements: Vec<Ast>,
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let explicit = f.alternate();
+        let mut statements = self.statements.iter().map(|statement| {
+            if explicit {
+                format!("{:#}", statement)
+            } else {
+                format!("{}", statement)
+            }
+        });
+
+        write!(f, "{}", statements.join("; "))
     }
 }
 
 #[derive(Snafu, Debug, Clone)]
 pub enum AstError {
     #[snafu(display("Expected next token, but got nothing"))]
-    ExpectedToken,
+    ExpectedToken { span: Span },
 
     #[snafu(display("Expected operator, but got token: {:?}", token))]
-    ExpectedOperator { token: Token },
+    ExpectedOperator { token: Token, span: Span },
 
     #[snafu(display("Unsupported unary operator: {:?}", operator))]
-    UnsupportedUnaryOperator { operator: Operator },
+    UnsupportedUnaryOperator { operator: Operator, span: Span },
 
     #[snafu(display("Unmatched closing parenthesis"))]
-    UnmatchedClosingParenthesis,
+    UnmatchedClosingParenthesis { span: Span },
 
     #[snafu(display(
         "Unmatched opening parenthesis, missing {} closing parenthesis",
         counter
     ))]
-    UnmatchedOpeningParenthesis { counter: usize },
+    UnmatchedOpeningParenthesis { counter: usize, span: Span },
+
+    #[snafu(display("Unmatched closing bracket"))]
+    UnmatchedClosingBracket { span: Span },
+
+    #[snafu(display("Unmatched opening bracket"))]
+    UnmatchedOpeningBracket { span: Span },
+
+    #[snafu(display("Interval expects lower and upper bound, but got {} values", count))]
+    InvalidInterval { count: usize, span: Span },
+
+    #[snafu(display("Expected percentage before `of`"))]
+    ExpectedPercentage { span: Span },
+
+    #[snafu(display("Expected `{}` in conditional expression", expected))]
+    IncompleteConditional { expected: &'static str, span: Span },
+
+    #[snafu(display("Can't assign value to constant {}", name))]
+    AssignmentToConstant { name: String, span: Span },
+
+    #[snafu(display("Can't redefine built-in function {}", name))]
+    BuiltinFunctionRedefinition { name: String, span: Span },
+
+    #[snafu(display("Invalid parameters of function {}, expected list of names", name))]
+    InvalidParameters { name: String, span: Span },
+
+    #[snafu(display("Duplicate parameter {} of function {}", parameter, name))]
+    DuplicateParameter {
+        name: String,
+        parameter: String,
+        span: Span,
+    },
+
+    #[snafu(display("Expected arguments of function call {}(...)", name))]
+    ExpectedFunctionArguments { name: String, span: Span },
+
+    #[snafu(display(
+        "Function {} expects {} arguments, but got {}",
+        function,
+        expected,
+        got
+    ))]
+    InvalidArgumentsCount {
+        function: Function,
+        expected: usize,
+        got: usize,
+        span: Span,
+    },
+}
+
+impl AstError {
+    pub fn span(&self) -> Span {
+        match self {
+            AstError::ExpectedToken { span }
+            | AstError::ExpectedOperator { span, .. }
+            | AstError::UnsupportedUnaryOperator { span, .. }
+            | AstError::UnmatchedClosingParenthesis { span }
+            | AstError::UnmatchedOpeningParenthesis { span, .. }
+            | AstError::UnmatchedClosingBracket { span }
+            | AstError::UnmatchedOpeningBracket { span }
+            | AstError::InvalidInterval { span, .. }
+            | AstError::ExpectedPercentage { span }
+            | AstError::IncompleteConditional { span, .. }
+            | AstError::AssignmentToConstant { span, .. }
+            | AstError::BuiltinFunctionRedefinition { span, .. }
+            | AstError::InvalidParameters { span, .. }
+            | AstError::DuplicateParameter { span, .. }
+            | AstError::ExpectedFunctionArguments { span, .. }
+            | AstError::InvalidArgumentsCount { span, .. } => *span,
+        }
+    }
 }
 
 pub struct AstBuilder {
-    token_iter: Peekable<VecIter<Token>>,
+    token_iter: Peekable<VecIter<Spanned<Token>>>,
+    /// Position after the last token, where missing tokens are reported.
+    end: usize,
+    /// Binding power of multiplication without operator.
+    implicit_precedence: usize,
+    errors: Vec<Error>,
 }
 
 impl AstBuilder {
     pub fn build_ast(s: &str) -> Result<Ast, Error> {
+        let (ast, errors) = AstBuilder::build_ast_recovering(s);
+
+        into_result(ast, errors)
+    }
+
+    /// Builds the tree, which may contain error nodes, reporting all errors ordered by position.
+    pub fn build_ast_recovering(s: &str) -> (Ast, Vec<Error>) {
         debug!("Starting to parse string {}", s);
 
-        let (_, tokens) = parse(s).map_err(|err| match err {
-            nom::Err::Failure(e) => e,
-            nom::Err::Error(e) => e,
-            _ => unimplemented!(),
-        })?;
+        let (tokens, errors) = parse_recovering(s);
+        let mut errors: Vec<Error> = errors.into_iter().map(Into::into).collect();
 
         debug!("Got tokens {:?}", tokens);
 
-        AstBuilder {
-            token_iter: tokens.into_iter().peekable(),
+        let ast = if tokens.is_empty() {
+            Ast::new(AstKind::Error, Span::new(0, s.len()))
+        } else {
+            AstBuilder::statement(tokens, &mut errors)
+        };
+
+        errors.sort_by_key(position);
+
+        (ast, errors)
+    }
+
+    pub fn build_program(s: &str) -> Result<Program, Error> {
+        let (program, errors) = AstBuilder::build_program_recovering(s);
+
+        into_result(program, errors)
+    }
+
+    /// Builds every statement of the program, skipping empty ones, and reports all errors
+    /// ordered by position.
+    pub fn build_program_recovering(s: &str) -> (Program, Vec<Error>) {
+        debug!("Starting to parse program {}", s);
+
+        let (tokens, errors) = parse_recovering(s);
+        let mut errors: Vec<Error> = errors.into_iter().map(Into::into).collect();
+
+        let statements: Vec<Ast> = tokens
+            .split(|token| match token.node {
+                Token::Separator => true,
+                _ => false,
+            })
+            .filter(|tokens| !tokens.is_empty())
+            .map(|tokens| AstBuilder::statement(tokens.to_vec(), &mut errors))
+            .collect();
+
+        if statements.is_empty() && errors.is_empty() {
+            errors.push(ParseError::User(ParseUserError::EmptyInput).into());
         }
-        .expr(0)
+
+        errors.sort_by_key(position);
+
+        (Program { statements }, errors)
     }
 
-    pub fn build_ast_from_tokens(tokens: Vec<Token>) -> Result<Ast, Error> {
-        AstBuilder {
+    /// Builds either assignment statement, function definition, or expression.
+    pub fn build_statement(tokens: Vec<Spanned<Token>>) -> Result<Ast, Error> {
+        let mut errors = Vec::new();
+        let ast = AstBuilder::statement(tokens, &mut errors);
+
+        into_result(ast, errors)
+    }
+
+    /// Builds expression, `after` is the position preceding tokens, which is reported when they are empty.
+    pub fn build_ast_from_tokens(tokens: Vec<Spanned<Token>>, after: usize) -> Result<Ast, Error> {
+        let mut errors = Vec::new();
+        let ast = AstBuilder::expression(tokens, after, &mut errors);
+
+        into_result(ast, errors)
+    }
+
+    fn statement(mut tokens: Vec<Spanned<Token>>, errors: &mut Vec<Error>) -> Ast {
+        let after = tokens.first().map_or(0, |token| token.span.start);
+
+        if let [Spanned {
+            node: Token::Identifier(name),
+            span: name_span,
+        }, Spanned {
+            node: Token::OpenParenthesis,
+            ..
+        }, ..] = tokens.as_slice()
+        {
+            let close = tokens.iter().position(|token| match token.node {
+                Token::CloseParenthesis => true,
+                _ => false,
+            });
+
+            if let Some(close) = close {
+                if let Some(Spanned {
+                    node: Token::Assign,
+                    span: assign_span,
+                }) = tokens.get(close + 1)
+                {
+                    let (name, name_span, assign_span) = (name.clone(), *name_span, *assign_span);
+                    let body = tokens.split_off(close + 2);
+                    let parameters = AstBuilder::build_parameters(
+                        (&name, name_span),
+                        &tokens[2..close],
+                        tokens[1].span.merge(tokens[close].span),
+                        errors,
+                    );
+                    let body = AstBuilder::expression(body, assign_span.end, errors);
+                    let span = name_span.merge(body.span);
+
+                    return Ast::new(
+                        AstKind::FunctionDefinition {
+                            name,
+                            parameters,
+                            body: Box::new(body),
+                        },
+                        span,
+                    );
+                }
+            }
+        }
+
+        match tokens.as_slice() {
+            [Spanned {
+                node: Token::Identifier(name),
+                span: name_span,
+            }, Spanned {
+                node: Token::Assign,
+                span: assign_span,
+            }, ..] => {
+                let (name, name_span, assign_span) = (name.clone(), *name_span, *assign_span);
+
+                if constants::is_constant(&name) {
+                    errors.push(
+                        AstError::AssignmentToConstant {
+                            name: name.clone(),
+                            span: name_span,
+                        }
+                        .into(),
+                    );
+                }
+
+                let value = AstBuilder::expression(tokens.split_off(2), assign_span.end, errors);
+                let span = name_span.merge(value.span);
+
+                Ast::new(
+                    AstKind::Assignment {
+                        name,
+                        value: Box::new(value),
+                    },
+                    span,
+                )
+            }
+            _ => AstBuilder::expression(tokens, after, errors),
+        }
+    }
+
+    /// Collects valid parameters, `span` covers them together with parenthesis.
+    fn build_parameters(
+        (name, name_span): (&str, Span),
+        tokens: &[Spanned<Token>],
+        span: Span,
+        errors: &mut Vec<Error>,
+    ) -> Vec<String> {
+        if Function::from_name(name).is_some() {
+            errors.push(
+                AstError::BuiltinFunctionRedefinition {
+                    name: name.to_owned(),
+                    span: name_span,
+                }
+                .into(),
+            );
+        }
+
+        let mut parameters: Vec<String> = Vec::new();
+
+        if tokens.is_empty() {
+            return parameters;
+        }
+
+        for parameter in tokens.split(|token| match token.node {
+            Token::Comma => true,
+            _ => false,
+        }) {
+            match parameter {
+                [Spanned {
+                    node: Token::Identifier(parameter),
+                    span: parameter_span,
+                }] => {
+                    if constants::is_constant(parameter) {
+                        errors.push(
+                            AstError::AssignmentToConstant {
+                                name: parameter.clone(),
+                                span: *parameter_span,
+                            }
+                            .into(),
+                        );
+                    } else if parameters.contains(parameter) {
+                        errors.push(
+                            AstError::DuplicateParameter {
+                                name: name.to_owned(),
+                                parameter: parameter.clone(),
+                                span: *parameter_span,
+                            }
+                            .into(),
+                        );
+                    } else {
+                        parameters.push(parameter.clone());
+                    }
+                }
+                _ => {
+                    errors.push(
+                        AstError::InvalidParameters {
+                            name: name.to_owned(),
+                            span,
+                        }
+                        .into(),
+                    );
+                    break;
+                }
+            }
+        }
+
+        parameters
+    }
+
+    fn expression(tokens: Vec<Spanned<Token>>, after: usize, errors: &mut Vec<Error>) -> Ast {
+        let end = tokens.last().map_or(after, |token| token.span.end);
+
+        let mut builder = AstBuilder {
             token_iter: tokens.into_iter().peekable(),
+            end,
+            implicit_precedence: Syntax::current().implicit_multiplication.precedence(),
+            errors: Vec::new(),
+        };
+        let ast = builder.expr(0);
+
+        // Separators of conditional expression stop it early, so they're left unconsumed
+        if let Some(token) = builder.token_iter.next() {
+            builder.errors.push(
+                AstError::ExpectedOperator {
+                    token: token.node,
+                    span: token.span,
+                }
+                .into(),
+            );
         }
-        .expr(0)
+
+        errors.append(&mut builder.errors);
+        ast
+    }
+
+    /// Records the error and returns node, which takes place of the invalid part.
+    fn error(&mut self, error: impl Into<Error>, span: Span) -> Ast {
+        self.errors.push(error.into());
+        Ast::new(AstKind::Error, span)
     }
 
-    fn nud(&mut self, t: Token) -> Result<Ast, Error> {
-        match t {
-            Token::Number(n) => Ok(Ast::Number(n)),
-            Token::Operator(operator) => match operator {
-                Operator::Plus | Operator::Minus => {
-                    let right = self.expr(0)?;
+    fn nud(&mut self, t: Spanned<Token>) -> Ast {
+        let span = t.span;
 
-                    Ok(Ast::UnaryOperator {
-                        child: Box::new(right),
-                        operator,
-                    })
+        match t.node {
+            // Unit suffix is a separate token, which directly follows the number
+            Token::Number(n) => match self.token_iter.peek().cloned() {
+                Some(Spanned {
+                    node: Token::Unit(unit),
+                    span: unit_span,
+                }) => {
+                    self.token_iter.next();
+                    Ast::new(AstKind::Quantity { value: n, unit }, span.merge(unit_span))
                 }
-                operator => Err(AstError::UnsupportedUnaryOperator { operator }.into()),
+                _ => Ast::new(AstKind::Number(n), span),
             },
-            Token::OpenParenthesis => {
-                let mut parenthesis = Vec::new();
-                let mut counter: usize = 1;
-
-                while let Some(token) = self.token_iter.next() {
-                    match &token {
-                        Token::OpenParenthesis => counter += 1,
-                        Token::CloseParenthesis => {
-                            match counter {
-                                1 => {
-                                    return AstBuilder::build_ast_from_tokens(parenthesis)
-                                        .map(|t| Ast::Parenthesis { child: Box::new(t) });
-                                }
-                                0 => return Err(AstError::UnmatchedClosingParenthesis.into()),
-                                _ => {}
-                            };
-                            counter -= 1;
-                        }
-                        _ => {}
-                    };
-                    parenthesis.push(token);
+            Token::Operator(operator) => {
+                let right = self.expr(operator.unary_precedence());
+                let child_span = span.merge(right.span);
+
+                match operator {
+                    Operator::Plus | Operator::Minus | Operator::BitNot | Operator::Not => {
+                        Ast::new(
+                            AstKind::UnaryOperator {
+                                child: Box::new(right),
+                                operator,
+                            },
+                            child_span,
+                        )
+                    }
+                    operator => self.error(
+                        AstError::UnsupportedUnaryOperator { operator, span },
+                        child_span,
+                    ),
+                }
+            }
+            Token::Identifier(name) => {
+                let open = match self.token_iter.peek() {
+                    Some(Spanned {
+                        node: Token::OpenParenthesis,
+                        ..
+                    }) => self.token_iter.next().unwrap().span,
+                    _ => {
+                        return if let Some(literal) = constants::literal(&name) {
+                            Ast::new(AstKind::Number(literal), span)
+                        } else if Function::from_name(&name).is_some() {
+                            self.error(AstError::ExpectedFunctionArguments { name, span }, span)
+                        } else {
+                            Ast::new(AstKind::Variable(name), span)
+                        };
+                    }
+                };
+
+                let (tokens, close) = self.parenthesis_tokens(open);
+                let span = span.merge(close);
+                let arguments = if tokens.is_empty() {
+                    Vec::new()
+                } else {
+                    let errors = &mut self.errors;
+
+                    split_arguments(tokens, open.end)
+                        .into_iter()
+                        .map(|(tokens, after)| AstBuilder::expression(tokens, after, errors))
+                        .collect()
+                };
+
+                match Function::from_name(&name) {
+                    None => Ast::new(AstKind::UserFunctionCall { name, arguments }, span),
+                    Some(function) if arguments.len() != function.arity() => self.error(
+                        AstError::InvalidArgumentsCount {
+                            function,
+                            expected: function.arity(),
+                            got: arguments.len(),
+                            span,
+                        },
+                        span,
+                    ),
+                    Some(function) => Ast::new(
+                        AstKind::FunctionCall {
+                            function,
+                            arguments,
+                        },
+                        span,
+                    ),
                 }
+            }
+            Token::OpenParenthesis => {
+                let (tokens, close) = self.parenthesis_tokens(span);
+                let child = AstBuilder::expression(tokens, span.end, &mut self.errors);
+
+                Ast::new(
+                    AstKind::Parenthesis {
+                        child: Box::new(child),
+                    },
+                    span.merge(close),
+                )
+            }
+            Token::OpenBracket => {
+                let (tokens, close) = self.bracket_tokens(span);
+                let interval_span = span.merge(close);
+                let errors = &mut self.errors;
+                let mut bounds: Vec<_> = if tokens.is_empty() {
+                    Vec::new()
+                } else {
+                    split_arguments(tokens, span.end)
+                        .into_iter()
+                        .map(|(tokens, after)| AstBuilder::expression(tokens, after, errors))
+                        .collect()
+                };
+
+                if bounds.len() == 2 {
+                    let upper = bounds.pop().unwrap();
+                    let lower = bounds.pop().unwrap();
 
-                if counter != 0 {
-                    Err(AstError::UnmatchedOpeningParenthesis { counter }.into())
+                    Ast::new(
+                        AstKind::Interval {
+                            lower: Box::new(lower),
+                            upper: Box::new(upper),
+                        },
+                        interval_span,
+                    )
                 } else {
-                    unreachable!()
+                    self.error(
+                        AstError::InvalidInterval {
+                            count: bounds.len(),
+                            span: interval_span,
+                        },
+                        interval_span,
+                    )
+                }
+            }
+            Token::If => {
+                let condition = self.expr(0);
+                let then = self.conditional_part(
+                    |token| match token {
+                        Token::Then => true,
+                        _ => false,
+                    },
+                    "then",
+                );
+                let otherwise = self.conditional_part(
+                    |token| match token {
+                        Token::Else => true,
+                        _ => false,
+                    },
+                    "else",
+                );
+
+                AstBuilder::conditional(condition, then, otherwise, span)
+            }
+            Token::Comma
+            | Token::Assign
+            | Token::Unit(_)
+            | Token::Conversion(_)
+            | Token::Percent
+            | Token::Then
+            | Token::Else
+            | Token::Question
+            | Token::Colon
+            | Token::Separator => self.error(AstError::ExpectedToken { span }, span),
+            Token::CloseParenthesis => {
+                self.error(AstError::UnmatchedClosingParenthesis { span }, span)
+            }
+            Token::CloseBracket => self.error(AstError::UnmatchedClosingBracket { span }, span),
+        }
+    }
+
+    /// Consumes separator of conditional expression and builds the part following it.
+    fn conditional_part(
+        &mut self,
+        is_separator: fn(&Token) -> bool,
+        expected: &'static str,
+    ) -> Ast {
+        let span = match self.token_iter.peek() {
+            Some(token) if is_separator(&token.node) => {
+                self.token_iter.next();
+                return self.expr(0);
+            }
+            Some(token) => token.span,
+            None => Span::at(self.end),
+        };
+
+        self.error(AstError::IncompleteConditional { expected, span }, span)
+    }
+
+    /// Builds conditional expression, `span` is the span of its first token.
+    fn conditional(condition: Ast, then: Ast, otherwise: Ast, span: Span) -> Ast {
+        let span = span.merge(condition.span).merge(otherwise.span);
+
+        Ast::new(
+            AstKind::Conditional {
+                condition: Box::new(condition),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+            },
+            span,
+        )
+    }
+
+    /// Collects tokens up to the closing parenthesis, which matches already consumed opening one.
+    /// Returns them together with span of the closing parenthesis.
+    /// When it's missing, all remaining tokens are taken, as if it was placed at the end.
+    fn parenthesis_tokens(&mut self, open: Span) -> (Vec<Spanned<Token>>, Span) {
+        let (tokens, close) = self.group_tokens(|token| match token {
+            Token::OpenParenthesis => Some(true),
+            Token::CloseParenthesis => Some(false),
+            _ => None,
+        });
+
+        match close {
+            Ok(close) => (tokens, close),
+            Err(counter) => {
+                self.errors.push(
+                    AstError::UnmatchedOpeningParenthesis {
+                        counter,
+                        span: open,
+                    }
+                    .into(),
+                );
+                (tokens, Span::at(self.end))
+            }
+        }
+    }
+
+    /// Same as `parenthesis_tokens`, but for brackets.
+    fn bracket_tokens(&mut self, open: Span) -> (Vec<Spanned<Token>>, Span) {
+        let (tokens, close) = self.group_tokens(|token| match token {
+            Token::OpenBracket => Some(true),
+            Token::CloseBracket => Some(false),
+            _ => None,
+        });
+
+        match close {
+            Ok(close) => (tokens, close),
+            Err(_) => {
+                self.errors
+                    .push(AstError::UnmatchedOpeningBracket { span: open }.into());
+                (tokens, Span::at(self.end))
+            }
+        }
+    }
+
+    /// Collects tokens of the group, `is_opening` tells whether token opens or closes a nested group.
+    /// Returns span of the closing token or number of unclosed groups, when it's missing.
+    fn group_tokens(
+        &mut self,
+        is_opening: impl Fn(&Token) -> Option<bool>,
+    ) -> (Vec<Spanned<Token>>, Result<Span, usize>) {
+        let mut tokens = Vec::new();
+        let mut counter: usize = 1;
+
+        while let Some(token) = self.token_iter.next() {
+            match is_opening(&token.node) {
+                Some(true) => counter += 1,
+                Some(false) => {
+                    if counter == 1 {
+                        return (tokens, Ok(token.span));
+                    }
+                    counter -= 1;
                 }
+                None => {}
+            };
+            tokens.push(token);
+        }
+
+        (tokens, Err(counter))
+    }
+
+    /// Whether operand follows `%`, which tells remainder `7 % 2` from percentage `7%`.
+    fn is_operand_next(&self, percent: Span) -> bool {
+        let mut tokens = self.token_iter.clone();
+        let next = match tokens.next() {
+            Some(token) => token,
+            None => return false,
+        };
+
+        match next.node {
+            Token::Number(_)
+            | Token::Identifier(_)
+            | Token::OpenParenthesis
+            | Token::OpenBracket
+            | Token::Operator(Operator::BitNot) => true,
+            // Sign is unary, when it's attached to the operand, but not to `%`, like in `7 % -2`
+            Token::Operator(Operator::Plus) | Token::Operator(Operator::Minus) => {
+                next.span.start > percent.end
+                    && tokens
+                        .next()
+                        .map_or(false, |operand| operand.span.start == next.span.end)
             }
-            Token::CloseParenthesis => Err(AstError::UnmatchedClosingParenthesis.into()),
+            _ => false,
         }
     }
 
-    fn led(&mut self, bp: usize, left: Ast, op: Token) -> Result<Ast, Error> {
-        match op {
+    fn is_postfix(&self, op: &Spanned<Token>) -> bool {
+        match op.node {
+            Token::Operator(operator) => operator.is_postfix(),
+            Token::Percent => !self.is_operand_next(op.span),
+            _ => false,
+        }
+    }
+
+    /// Builds postfix operator, which applies to the already built left operand.
+    fn postfix(&mut self, left: Ast, op: Spanned<Token>) -> Ast {
+        let span = left.span.merge(op.span);
+        let child = Box::new(left);
+
+        match op.node {
             Token::Operator(operator) => {
-                let right = self.expr(bp)?;
+                Ast::new(AstKind::PostfixOperator { operator, child }, span)
+            }
+            _ => Ast::new(AstKind::Percent { child }, span),
+        }
+    }
 
-                Ok(Ast::BinaryOperator {
-                    left: Box::new(left),
-                    right: Box::new(right),
-                    operator,
-                })
+    /// Builds infix operator, recognizing percentages like `200 + 10%` and `50% of 80`.
+    fn binary(&mut self, bp: usize, left: Ast, operator: Operator) -> Ast {
+        let right = self.expr(if operator.is_right_associative() {
+            bp - 1
+        } else {
+            bp
+        });
+        let span = left.span.merge(right.span);
+
+        let kind = match (operator, left, right) {
+            (
+                Operator::Plus,
+                base,
+                Ast {
+                    kind: AstKind::Percent { child },
+                    ..
+                },
+            )
+            | (
+                Operator::Minus,
+                base,
+                Ast {
+                    kind: AstKind::Percent { child },
+                    ..
+                },
+            ) => AstKind::PercentChange {
+                operator,
+                base: Box::new(base),
+                percent: child,
+            },
+            (
+                Operator::Of,
+                Ast {
+                    kind: AstKind::Percent { child },
+                    ..
+                },
+                whole,
+            ) => AstKind::PercentOf {
+                percent: child,
+                whole: Box::new(whole),
+            },
+            (Operator::Of, left, _) => {
+                return self.error(AstError::ExpectedPercentage { span: left.span }, span)
+            }
+            (operator, left, right) => AstKind::BinaryOperator {
+                left: Box::new(left),
+                right: Box::new(right),
+                operator,
+                implicit: false,
+            },
+        };
+
+        Ast::new(kind, span)
+    }
+
+    fn led(&mut self, bp: usize, left: Ast, op: Spanned<Token>) -> Ast {
+        match op.node {
+            Token::Operator(operator) if operator.is_infix() => self.binary(bp, left, operator),
+            // Percent sign followed by operand
+            Token::Percent => self.binary(bp, left, Operator::Remainder),
+            Token::Question => {
+                let then = self.expr(0);
+                let otherwise = self.conditional_part(
+                    |token| match token {
+                        Token::Colon => true,
+                        _ => false,
+                    },
+                    ":",
+                );
+                let span = left.span;
+
+                AstBuilder::conditional(left, then, otherwise, span)
+            }
+            Token::Conversion(unit) => {
+                let span = left.span.merge(op.span);
+                Ast::new(
+                    AstKind::Conversion {
+                        child: Box::new(left),
+                        unit,
+                    },
+                    span,
+                )
+            }
+            token => {
+                self.errors.push(
+                    AstError::ExpectedOperator {
+                        token: token.clone(),
+                        span: op.span,
+                    }
+                    .into(),
+                );
+
+                // Operand without operator is still built to find errors inside of it.
+                let span = match token {
+                    Token::Comma
+                    | Token::Assign
+                    | Token::Separator
+                    | Token::CloseParenthesis
+                    | Token::CloseBracket => op.span,
+                    token => self.nud(Spanned::new(token, op.span)).span,
+                };
+                Ast::new(AstKind::Error, left.span.merge(span))
             }
-            token => Err(AstError::ExpectedOperator { token }.into()),
         }
     }
 
-    fn expr(&mut self, rbp: usize) -> Result<Ast, Error> {
-        let first_token = self.token_iter.next().context(ExpectedToken)?;
-        let mut left = self.nud(first_token)?;
+    fn expr(&mut self, rbp: usize) -> Ast {
+        let first_token = match self.token_iter.next() {
+            Some(token) => token,
+            None => {
+                let span = Span::at(self.end);
+                return self.error(AstError::ExpectedToken { span }, span);
+            }
+        };
+        let mut left = self.nud(first_token);
 
         while let Some(peeked) = self.token_iter.peek() {
-            if rbp >= peeked.precedence() {
+            // Operand right after another one is multiplied by it, e.g. `2x` or `(a)(b)`
+            let implicit = match peeked.node {
+                Token::Identifier(_) | Token::OpenParenthesis | Token::OpenBracket => true,
+                _ => false,
+            };
+            let precedence = if implicit {
+                self.implicit_precedence
+            } else {
+                peeked.node.precedence()
+            };
+
+            if rbp >= precedence {
                 break;
             }
 
-            let op = self.token_iter.next().unwrap();
-            left = self.led(op.precedence(), left, op)?;
+            left = if implicit {
+                let right = self.expr(precedence);
+                let span = left.span.merge(right.span);
+
+                Ast::new(
+                    AstKind::BinaryOperator {
+                        operator: Operator::Multiply,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                        implicit: true,
+                    },
+                    span,
+                )
+            } else {
+                let op = self.token_iter.next().unwrap();
+
+                if self.is_postfix(&op) {
+                    self.postfix(left, op)
+                } else {
+                    self.led(op.node.precedence(), left, op)
+                }
+            };
         }
 
-        Ok(left)
+        left
     }
 }
 
+/// Position used to order errors, errors without span go last.
+fn position(error: &Error) -> usize {
+    error.span().map_or(usize::max_value(), |span| span.start)
+}
+
+/// Returns the first error by position, if there are any.
+fn into_result<T>(ast: T, errors: Vec<Error>) -> Result<T, Error> {
+    match errors.into_iter().min_by_key(position) {
+        Some(error) => Err(error),
+        None => Ok(ast),
+    }
+}
+
+/// Splits tokens of function arguments by commas, which are not nested in parenthesis or brackets.
+/// Each argument is paired with the position preceding it.
+fn split_arguments(tokens: Vec<Spanned<Token>>, after: usize) -> Vec<(Vec<Spanned<Token>>, usize)> {
+    let mut arguments = vec![(Vec::new(), after)];
+    let mut depth: usize = 0;
+
+    for token in tokens {
+        match &token.node {
+            Token::OpenParenthesis | Token::OpenBracket => depth += 1,
+            // Group of the other kind may be unbalanced
+            Token::CloseParenthesis | Token::CloseBracket => depth = depth.saturating_sub(1),
+            Token::Comma if depth == 0 => {
+                arguments.push((Vec::new(), token.span.end));
+                continue;
+            }
+            _ => {}
+        };
+        arguments.last_mut().unwrap().0.push(token);
+    }
+
+    arguments
+}
+
 pub fn build_ast(s: impl AsRef<str>) -> Result<Ast, Error> {
     AstBuilder::build_ast(s.as_ref())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::AstBuilder;
+    use super::{AstBuilder, AstKind};
+    use crate::span::Span;
+    use crate::syntax::{ImplicitMultiplication, Syntax};
 
     fn test_expr(s: &str) {
         assert_eq!(s, format!("{}", AstBuilder::build_ast(s).unwrap()));
@@ -227,8 +1211,254 @@ mod tests {
         test_expr("1 * ( 2 * ( 3 * ( 4 * 5 ) ) )");
     }
 
+    #[test]
+    fn test_power() {
+        test_expr("2 ^ 3 ^ 2");
+        test_expr("-2 ^ 2");
+        test_expr("2 * 3 ^ 2");
+        assert_eq!(
+            "2 ^ 3",
+            format!("{}", AstBuilder::build_ast("2 ** 3").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_factorial() {
+        test_expr("-3! + 2 ^ 3!");
+        test_expr("( 1 + 2 )! * nCr(5, 2)");
+        check_error_type(
+            "3 ! 2",
+            "AstError(ExpectedOperator { token: Number(Decimal(\"2\")), span: Span { start: 4, end: 5 } })",
+        );
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        test_expr("2 x ^ 2 + 3 ( 4 + 5 )");
+        test_expr("( a ) ( b ) sqrt(2)");
+        let explicit = |s| format!("{:#}", AstBuilder::build_ast(s).unwrap());
+        assert_eq!("(2 * (x ^ 2)) + (3 * ( 4 + 5 ))", explicit("2x^2 + 3(4+5)"));
+        assert_eq!("(1 / 2) * x", explicit("1/2x"));
+        assert_eq!(
+            "1 / (2 * x)",
+            Syntax {
+                implicit_multiplication: ImplicitMultiplication::Tight,
+                ..Syntax::default()
+            }
+            .scope(|| explicit("1/2x"))
+        );
+        check_error_type(
+            "x 2",
+            "AstError(ExpectedOperator { token: Number(Decimal(\"2\")), span: Span { start: 2, end: 3 } })",
+        );
+    }
+
+    #[test]
+    fn test_conditional() {
+        test_expr("x > 3 and y <= 2 or not x == y");
+        test_expr("if x != 0 then 1 / x else if y then 2 else 3");
+        assert_eq!(
+            "if x < 0 then -x else x + 1",
+            format!("{}", AstBuilder::build_ast("x < 0 ? -x : x + 1").unwrap())
+        );
+        assert_eq!(
+            "if a then if b then 1 else 2 else 3",
+            format!("{}", AstBuilder::build_ast("a ? b ? 1 : 2 : 3").unwrap())
+        );
+        check_error_type(
+            "if x then 1",
+            "AstError(IncompleteConditional { expected: \"else\", span: Span { start: 11, end: 11 } })",
+        );
+        check_error_type(
+            "1 : 2",
+            "AstError(ExpectedOperator { token: Colon, span: Span { start: 2, end: 3 } })",
+        );
+    }
+
+    #[test]
+    fn test_program() {
+        assert_eq!(
+            "a = 2; b = a * 3; b + 1",
+            format!(
+                "{}",
+                AstBuilder::build_program("a = 2;b = a*3\n\n b + 1;").unwrap()
+            )
+        );
+        let program = AstBuilder::build_program("f(x) = x\r\nf(2)").unwrap();
+        assert_eq!(program.statements[1].span, Span::new(10, 14));
+        check_error_type(
+            "1; 2",
+            "AstError(ExpectedOperator { token: Separator, span: Span { start: 1, end: 2 } })",
+        );
+        let (program, errors) = AstBuilder::build_program_recovering("1 +; * 2");
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_function_call() {
+        test_expr("sqrt(2) + 1");
+        test_expr("max(1, 2 * ( 3 + 4 ))");
+        test_expr("-sin(cos(0)) ^ 2");
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            format!("2 * {}", std::f64::consts::PI),
+            format!("{}", AstBuilder::build_ast("2 * pi").unwrap())
+        );
+        assert_eq!(
+            format!("{} ^ 2", std::f64::consts::E),
+            format!("{}", AstBuilder::build_ast("e ^ 2").unwrap())
+        );
+        test_expr("3 + 2.5i * i");
+
+        // `i` is a variable, unless it's the imaginary unit of complex mode
+        assert!(AstBuilder::build_program("i = 3; f(i) = i ^ 2").is_ok());
+        let complex = Syntax {
+            imaginary: true,
+            ..Syntax::default()
+        };
+        assert!(complex
+            .scope(|| AstBuilder::build_program("i = 3"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_assignment() {
+        test_expr("x = 3 * 4");
+        test_expr("x / 2");
+        test_expr("radius = sqrt(area / 2)");
+    }
+
+    #[test]
+    fn test_function_definition() {
+        test_expr("f(x, y) = x ^ 2 + y");
+        test_expr("g() = 42");
+        test_expr("f(1, 2) * g()");
+        assert_eq!(
+            vec!["f".to_owned(), "g".to_owned()],
+            AstBuilder::build_ast("f(g(1), f(2, 3))")
+                .unwrap()
+                .user_function_calls()
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let s = "f(x) = sqrt(x + 1) * 2";
+        let ast = AstBuilder::build_ast(s).unwrap();
+        assert_eq!(ast.span, Span::new(0, s.len()));
+
+        match ast.kind {
+            AstKind::FunctionDefinition { body, .. } => {
+                assert_eq!(&s[body.span.start..body.span.end], "sqrt(x + 1) * 2");
+
+                let mut spans = Vec::new();
+                body.visit(&mut |node| spans.push(&s[node.span.start..node.span.end]));
+                assert_eq!(
+                    spans,
+                    vec!["sqrt(x + 1) * 2", "sqrt(x + 1)", "x + 1", "x", "1", "2"]
+                );
+            }
+            kind => panic!("Unexpected AST {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_interval() {
+        test_expr("[1, 2] * 3 ± 0.5");
+        test_expr("[-max(1, 2), [0, 1]]");
+
+        let (_, errors) = AstBuilder::build_ast_recovering("[1] + [2, (3]");
+        assert_eq!(
+            vec![
+                "AstError(InvalidInterval { count: 1, span: Span { start: 0, end: 3 } })",
+                "AstError(UnmatchedOpeningParenthesis { counter: 1, span: Span { start: 10, end: 11 } })",
+            ],
+            errors
+                .iter()
+                .map(|error| format!("{:?}", error))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_percent() {
+        test_expr("200 + 10%");
+        test_expr("50% of 80 - 5%");
+        test_expr("7 % 2 * 3%");
+        test_expr("x % ( 2 + 1 )");
+        test_expr("7 % -2 + 10% - 5");
+        assert_eq!(
+            "7 % 2",
+            format!("{}", AstBuilder::build_ast("7 mod 2").unwrap())
+        );
+        check_error_type(
+            "5 of 80",
+            "AstError(ExpectedPercentage { span: Span { start: 0, end: 1 } })",
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let (ast, errors) = AstBuilder::build_ast_recovering("(1 + * 2 $ , sqrt 3");
+
+        assert_eq!("( 1 + <error> <error> )", format!("{}", ast));
+        assert_eq!(
+            vec![
+                "AstError(UnmatchedOpeningParenthesis { counter: 1, span: Span { start: 0, end: 1 } })",
+                "AstError(UnsupportedUnaryOperator { operator: Multiply, span: Span { start: 5, end: 6 } })",
+                "ParseError(User(UnexpectedCharacter { character: '$', span: Span { start: 9, end: 10 } }))",
+                "AstError(ExpectedOperator { token: Comma, span: Span { start: 11, end: 12 } })",
+                "AstError(ExpectedFunctionArguments { name: \"sqrt\", span: Span { start: 13, end: 17 } })",
+                "AstError(ExpectedOperator { token: Number(Decimal(\"3\")), span: Span { start: 18, end: 19 } })",
+            ],
+            errors
+                .iter()
+                .map(|error| format!("{:?}", error))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn check_failing() {
-        check_error_type("b", "ParseError(Nom((\"b\", Many1)))");
+        check_error_type(
+            "$",
+            "ParseError(User(UnexpectedCharacter { character: '$', span: Span { start: 0, end: 1 } }))",
+        );
+        check_error_type(
+            "pi = 3",
+            "AstError(AssignmentToConstant { name: \"pi\", span: Span { start: 0, end: 2 } })",
+        );
+        check_error_type(
+            "x = ",
+            "AstError(ExpectedToken { span: Span { start: 3, end: 3 } })",
+        );
+        check_error_type(
+            "1 + (2 * ",
+            "AstError(UnmatchedOpeningParenthesis { counter: 1, span: Span { start: 4, end: 5 } })",
+        );
+        check_error_type(
+            "sqrt(x) = x",
+            "AstError(BuiltinFunctionRedefinition { name: \"sqrt\", span: Span { start: 0, end: 4 } })",
+        );
+        check_error_type(
+            "f(x, 1) = x",
+            "AstError(InvalidParameters { name: \"f\", span: Span { start: 1, end: 7 } })",
+        );
+        check_error_type(
+            "f(x, x) = x",
+            "AstError(DuplicateParameter { name: \"f\", parameter: \"x\", span: Span { start: 5, end: 6 } })",
+        );
+        check_error_type(
+            "sqrt 2",
+            "AstError(ExpectedFunctionArguments { name: \"sqrt\", span: Span { start: 0, end: 4 } })",
+        );
+        check_error_type(
+            "2 * max(1)",
+            "AstError(InvalidArgumentsCount { function: Max, expected: 2, got: 1, span: Span { start: 4, end: 10 } })",
+        );
     }
 }
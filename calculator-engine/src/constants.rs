@@ -0,0 +1,105 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use crate::number::Literal;
+use crate::syntax::Syntax;
+use std::f64::consts;
+
+pub const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", consts::PI),
+    ("e", consts::E),
+    ("tau", 2.0 * consts::PI),
+    ("inf", std::f64::INFINITY),
+    ("nan", std::f64::NAN),
+    // Booleans, which are results of comparisons
+    ("true", 1.0),
+    ("false", 0.0),
+];
+
+pub fn lookup(name: &str) -> Option<f64> {
+    CONSTANTS
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, value)| *value)
+}
+
+/// Whether the name can't be assigned, which includes `i`, when it's the imaginary unit.
+pub fn is_constant(name: &str) -> bool {
+    lookup(name).is_some() || (name == "i" && Syntax::current().imaginary)
+}
+
+pub fn literal(name: &str) -> Option<Literal> {
+    match name {
+        "i" if Syntax::current().imaginary => return Some(Literal::Imaginary("1".to_owned())),
+        // Written as decimals, so they are exact in every number type
+        "true" => return Some(Literal::Decimal("1".to_owned())),
+        "false" => return Some(Literal::Decimal("0".to_owned())),
+        _ => {}
+    }
+
+    CONSTANTS
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(constant, _)| Literal::Constant(constant))
+}
+
+/// Finds the most similar name, which is close enough to be a typo.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2 && *distance < name.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub fn closest_constant(name: &str) -> Option<&'static str> {
+    closest(name, CONSTANTS.iter().map(|(constant, _)| *constant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(Some(consts::PI), lookup("pi"));
+        assert!(lookup("nan").unwrap().is_nan());
+        assert_eq!(None, lookup("pie"));
+
+        assert!(!is_constant("i"));
+        let complex = Syntax {
+            imaginary: true,
+            ..Syntax::default()
+        };
+        assert!(complex.scope(|| is_constant("i")));
+    }
+
+    #[test]
+    fn test_closest() {
+        assert_eq!(Some("pi"), closest_constant("pie"));
+        assert_eq!(Some("tau"), closest_constant("tua"));
+        assert_eq!(None, closest_constant("b"));
+        assert_eq!(None, closest_constant("velocity"));
+    }
+}
@@ -0,0 +1,410 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Rendering of errors in the style of rustc, with the offending line and underline.
+
+use crate::ast::{AstBuilder, AstError};
+use crate::errors::Error;
+use crate::execution::environment::EnvironmentError;
+use crate::execution::interpret::InterpreterError;
+use crate::execution::jit::JitError;
+use crate::number::NumberError;
+use crate::parser::{ParseError, ParseUserError};
+use crate::span::Span;
+use ansi_term::{Color, Style};
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    Plain,
+    Ansi,
+}
+
+impl ColorMode {
+    fn paint(self, style: Style, text: &str) -> String {
+        match self {
+            ColorMode::Plain => text.to_owned(),
+            ColorMode::Ansi => style.paint(text).to_string(),
+        }
+    }
+}
+
+/// Suggestion how to fix the error, optionally pointing to the place of the fix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Help {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub help: Option<Help>,
+}
+
+impl Diagnostic {
+    pub fn new(source: &str, error: &Error) -> Diagnostic {
+        Diagnostic {
+            code: code(error),
+            message: error.to_string(),
+            span: error.span(),
+            help: help(source, error),
+        }
+    }
+
+    /// Renders multi-line report, which ends with a new line.
+    pub fn render(&self, source: &str, mode: ColorMode) -> String {
+        self.render_in(source, None, mode)
+    }
+
+    /// Same as `render`, but the location is prefixed by name of the file containing the source.
+    pub fn render_in_file(&self, source: &str, file: &str, mode: ColorMode) -> String {
+        self.render_in(source, Some(file), mode)
+    }
+
+    fn render_in(&self, source: &str, file: Option<&str>, mode: ColorMode) -> String {
+        let error_style = Color::Red.bold();
+        let help_style = Color::Cyan.bold();
+
+        let mut report = String::new();
+
+        writeln!(
+            report,
+            "{}{}",
+            mode.paint(error_style, &format!("error[{}]", self.code)),
+            mode.paint(Style::new().bold(), &format!(": {}", self.message))
+        )
+        .unwrap();
+
+        if let Some(span) = self.span {
+            let (line, column) = location(source, span.start);
+            let location = match file {
+                Some(file) => format!("{}:{}:{}", file, line, column),
+                None => format!("{}:{}", line, column),
+            };
+            render_snippet(
+                &mut report,
+                source,
+                span,
+                error_style,
+                Some(&location),
+                mode,
+            );
+        }
+
+        if let Some(help) = &self.help {
+            writeln!(
+                report,
+                "{}: {}",
+                mode.paint(help_style, "help"),
+                help.message
+            )
+            .unwrap();
+
+            if let Some(span) = help.span {
+                render_snippet(&mut report, source, span, help_style, None, mode);
+            }
+        }
+
+        report
+    }
+}
+
+pub fn render(source: &str, error: &Error, mode: ColorMode) -> String {
+    Diagnostic::new(source, error).render(source, mode)
+}
+
+pub fn render_in_file(source: &str, file: &str, error: &Error, mode: ColorMode) -> String {
+    Diagnostic::new(source, error).render_in_file(source, file, mode)
+}
+
+/// Line and column of the position in the source, both are counted from one.
+pub fn location(source: &str, position: usize) -> (usize, usize) {
+    let position = position.min(source.len());
+    let line_start = source[..position].rfind('\n').map_or(0, |i| i + 1);
+
+    (
+        source[..position].matches('\n').count() + 1,
+        source[line_start..position].chars().count() + 1,
+    )
+}
+
+/// Finds all syntax errors in the source, without evaluating it.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    AstBuilder::build_program_recovering(source)
+        .1
+        .iter()
+        .map(|error| Diagnostic::new(source, error))
+        .collect()
+}
+
+/// Writes location, source line and `^^^` underline of the span.
+fn render_snippet(
+    report: &mut String,
+    source: &str,
+    span: Span,
+    style: Style,
+    location_text: Option<&str>,
+    mode: ColorMode,
+) {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+
+    let (line_number, column) = location(source, start);
+    let (line_number, column) = (line_number.to_string(), column - 1);
+    let width = source[start..span.end.max(start).min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    let gutter_style = Color::Blue.bold();
+    let padding = " ".repeat(line_number.len());
+
+    if let Some(location_text) = location_text {
+        writeln!(
+            report,
+            "{}{} {}",
+            padding,
+            mode.paint(gutter_style, "-->"),
+            location_text
+        )
+        .unwrap();
+    }
+
+    let gutter = mode.paint(gutter_style, &format!("{} |", padding));
+    writeln!(report, "{}", gutter).unwrap();
+    writeln!(
+        report,
+        "{} {}",
+        mode.paint(gutter_style, &format!("{} |", line_number)),
+        line
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "{} {}{}",
+        gutter,
+        " ".repeat(column),
+        mode.paint(style, &"^".repeat(width))
+    )
+    .unwrap();
+}
+
+fn code(error: &Error) -> &'static str {
+    match error {
+        Error::ParseError(error) => match error {
+            ParseError::Nom(_) => "E0001",
+            ParseError::User(ParseUserError::InvalidOperator { .. }) => "E0002",
+            ParseError::User(ParseUserError::UnexpectedCharacter { .. }) => "E0003",
+            ParseError::User(ParseUserError::EmptyInput) => "E0004",
+            ParseError::User(ParseUserError::InvalidDigit { .. }) => "E0005",
+        },
+        Error::AstError(error) => match error {
+            AstError::ExpectedToken { .. } => "E0101",
+            AstError::ExpectedOperator { .. } => "E0102",
+            AstError::UnsupportedUnaryOperator { .. } => "E0103",
+            AstError::UnmatchedClosingParenthesis { .. } => "E0104",
+            AstError::UnmatchedOpeningParenthesis { .. } => "E0105",
+            AstError::AssignmentToConstant { .. } => "E0106",
+            AstError::BuiltinFunctionRedefinition { .. } => "E0107",
+            AstError::InvalidParameters { .. } => "E0108",
+            AstError::DuplicateParameter { .. } => "E0109",
+            AstError::ExpectedFunctionArguments { .. } => "E0110",
+            AstError::InvalidArgumentsCount { .. } => "E0111",
+            AstError::UnmatchedClosingBracket { .. } => "E0112",
+            AstError::UnmatchedOpeningBracket { .. } => "E0113",
+            AstError::InvalidInterval { .. } => "E0114",
+            AstError::ExpectedPercentage { .. } => "E0115",
+            AstError::IncompleteConditional { .. } => "E0116",
+        },
+        Error::InterpreterError(error) => match error {
+            InterpreterError::InvalidUnaryOperator { .. } => "E0201",
+            InterpreterError::NestedStatement { .. } => "E0202",
+            InterpreterError::ErrorNode { .. } => "E0203",
+            InterpreterError::Arithmetic { source, .. } => match source {
+                NumberError::InvalidLiteral { .. } => "E0501",
+                NumberError::DivisionByZero => "E0502",
+                NumberError::NotExact { .. } => "E0503",
+                NumberError::Undefined { .. } => "E0504",
+                NumberError::Unsupported { .. } => "E0505",
+                NumberError::Overflow { .. } => "E0506",
+                NumberError::NotInteger { .. } => "E0507",
+                NumberError::DimensionMismatch { .. } => "E0508",
+            },
+        },
+        Error::JitError(error) => match error {
+            JitError::UnsupportedUnaryOperator { .. } => "E0301",
+            JitError::NestedStatement { .. } => "E0302",
+            JitError::ErrorNode { .. } => "E0303",
+            JitError::UnsupportedExpression { .. } => "E0304",
+            JitError::Arithmetic { .. } => "E0305",
+        },
+        Error::EnvironmentError(error) => match error {
+            EnvironmentError::UndefinedVariable { .. } => "E0401",
+            EnvironmentError::UndefinedFunction { .. } => "E0402",
+            EnvironmentError::InvalidArgumentsCount { .. } => "E0403",
+            EnvironmentError::RecursiveFunction { .. } => "E0404",
+        },
+    }
+}
+
+fn help(source: &str, error: &Error) -> Option<Help> {
+    let (message, span) = match error {
+        Error::AstError(AstError::UnmatchedOpeningParenthesis { counter, span }) => (
+            format!(
+                "missing {} closing parenthesis, insert `{}` here",
+                counter,
+                ")".repeat(*counter)
+            ),
+            Some(Span::at(statement_end(source, span.start))),
+        ),
+        Error::AstError(AstError::UnmatchedClosingParenthesis { .. }) => {
+            ("remove it or insert `(` before it".to_owned(), None)
+        }
+        Error::AstError(AstError::UnmatchedOpeningBracket { span }) => (
+            "insert `]` here".to_owned(),
+            Some(Span::at(statement_end(source, span.start))),
+        ),
+        Error::AstError(AstError::UnmatchedClosingBracket { .. }) => {
+            ("remove it or insert `[` before it".to_owned(), None)
+        }
+        Error::AstError(AstError::InvalidInterval { .. }) => {
+            ("write interval as `[lower, upper]`".to_owned(), None)
+        }
+        Error::AstError(AstError::ExpectedPercentage { .. }) => {
+            ("write percentage like `50% of 80`".to_owned(), None)
+        }
+        Error::AstError(AstError::IncompleteConditional { expected, .. }) => (
+            if *expected == ":" {
+                "write conditional like `x > 0 ? x : -x`".to_owned()
+            } else {
+                "write conditional like `if x > 0 then x else -x`".to_owned()
+            },
+            None,
+        ),
+        Error::AstError(AstError::AssignmentToConstant { name, .. }) => (
+            format!("`{}` is a built-in constant, choose another name", name),
+            None,
+        ),
+        Error::AstError(AstError::BuiltinFunctionRedefinition { name, .. }) => (
+            format!("`{}` is a built-in function, choose another name", name),
+            None,
+        ),
+        Error::AstError(AstError::ExpectedFunctionArguments { name, .. }) => (
+            format!("put arguments in parenthesis: `{}(...)`", name),
+            None,
+        ),
+        Error::EnvironmentError(EnvironmentError::RecursiveFunction { .. }) => {
+            ("recursive functions aren't supported".to_owned(), None)
+        }
+        _ => return None,
+    };
+
+    Some(Help { message, span })
+}
+
+/// End of the statement containing the position, excluding trailing whitespace and comment.
+fn statement_end(source: &str, position: usize) -> usize {
+    let end = source[position..]
+        .find(|c| c == ';' || c == '\n' || c == '#')
+        .map_or(source.len(), |i| position + i);
+
+    source[..end].trim_end().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::Interpreter;
+
+    fn render_plain(s: &str) -> String {
+        render(
+            s,
+            &Interpreter::exec(s, &mut Environment::new()).unwrap_err(),
+            ColorMode::Plain,
+        )
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render_plain("1 + (2 * 3"),
+            "error[E0105]: Unmatched opening parenthesis, missing 1 closing parenthesis\n \
+             --> 1:5\n  \
+             |\n\
+             1 | 1 + (2 * 3\n  \
+             |     ^\n\
+             help: missing 1 closing parenthesis, insert `)` here\n  \
+             |\n\
+             1 | 1 + (2 * 3\n  \
+             |           ^\n"
+        );
+        assert_eq!(
+            render_plain("2 * radius"),
+            "error[E0401]: Undefined variable: radius\n \
+             --> 1:5\n  \
+             |\n\
+             1 | 2 * radius\n  \
+             |     ^^^^^^\n"
+        );
+        assert_eq!(render_plain(""), "error[E0004]: Empty expression\n");
+
+        let source = "x = 1\n# comment\ny = x +";
+        assert_eq!(
+            render_in_file(
+                source,
+                "sheet.calc",
+                &Interpreter::exec(source, &mut Environment::new()).unwrap_err(),
+                ColorMode::Plain
+            ),
+            "error[E0101]: Expected next token, but got nothing\n \
+             --> sheet.calc:3:8\n  \
+             |\n\
+             3 | y = x +\n  \
+             |        ^\n"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check("f(x) = 2 * x").is_empty());
+        assert_eq!(
+            check("a = (1 # note\nb = 2")[0].help.as_ref().unwrap().span,
+            Some(Span::at(6))
+        );
+        assert_eq!(
+            check("(1 + ) * $")
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            vec!["E0101", "E0101", "E0003"]
+        );
+    }
+}
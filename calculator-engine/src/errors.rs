@@ -22,9 +22,11 @@
  */
 
 use super::ast::AstError;
+use super::execution::environment::EnvironmentError;
 use super::execution::interpret::InterpreterError;
 use super::execution::jit::JitError;
-use super::parser::ParseError;
+use super::parser::{ParseError, ParseUserError};
+use super::span::Span;
 use derive_more::{Display, From};
 
 #[derive(Debug, From, Display)]
@@ -33,4 +35,25 @@ pub enum Error {
     AstError(AstError),
     InterpreterError(InterpreterError),
     JitError(JitError),
+    EnvironmentError(EnvironmentError),
+}
+
+impl Error {
+    /// Location of the error in the source string, when it's known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::ParseError(ParseError::User(ParseUserError::UnexpectedCharacter {
+                span,
+                ..
+            }))
+            | Error::ParseError(ParseError::User(ParseUserError::InvalidDigit { span, .. })) => {
+                Some(*span)
+            }
+            Error::ParseError(_) => None,
+            Error::AstError(error) => Some(error.span()),
+            Error::InterpreterError(error) => Some(error.span()),
+            Error::JitError(error) => Some(error.span()),
+            Error::EnvironmentError(error) => error.span(),
+        }
+    }
 }
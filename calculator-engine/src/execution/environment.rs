@@ -0,0 +1,375 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use crate::ast::{Ast, AstKind};
+use crate::constants;
+use crate::number::Number;
+use crate::span::Span;
+use snafu::Snafu;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Snafu, Debug, Clone, PartialEq)]
+pub enum EnvironmentError {
+    #[snafu(display(
+        "Undefined variable: {}{}",
+        name,
+        suggestion
+            .as_ref()
+            .map(|suggestion| format!(", did you mean {}?", suggestion))
+            .unwrap_or_default()
+    ))]
+    UndefinedVariable {
+        name: String,
+        suggestion: Option<String>,
+        span: Option<Span>,
+    },
+
+    #[snafu(display(
+        "Undefined function: {}{}",
+        name,
+        suggestion
+            .as_ref()
+            .map(|suggestion| format!(", did you mean {}?", suggestion))
+            .unwrap_or_default()
+    ))]
+    UndefinedFunction {
+        name: String,
+        suggestion: Option<String>,
+        span: Option<Span>,
+    },
+
+    #[snafu(display("Function {} expects {} arguments, but got {}", name, expected, got))]
+    InvalidArgumentsCount {
+        name: String,
+        expected: usize,
+        got: usize,
+        span: Option<Span>,
+    },
+
+    #[snafu(display("Function {} can't call itself", name))]
+    RecursiveFunction { name: String, span: Option<Span> },
+}
+
+impl EnvironmentError {
+    /// Attaches location of the expression, which caused the error.
+    pub fn at(mut self, location: Span) -> EnvironmentError {
+        match &mut self {
+            EnvironmentError::UndefinedVariable { span, .. }
+            | EnvironmentError::UndefinedFunction { span, .. }
+            | EnvironmentError::InvalidArgumentsCount { span, .. }
+            | EnvironmentError::RecursiveFunction { span, .. } => *span = Some(location),
+        }
+        self
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EnvironmentError::UndefinedVariable { span, .. }
+            | EnvironmentError::UndefinedFunction { span, .. }
+            | EnvironmentError::InvalidArgumentsCount { span, .. }
+            | EnvironmentError::RecursiveFunction { span, .. } => *span,
+        }
+    }
+}
+
+/// Function defined by user, which is immutable once defined.
+/// Redefinition creates a new function with a new unique id.
+#[derive(Debug)]
+pub struct UserFunction {
+    pub id: u64,
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Ast,
+    /// Names of user functions, which are called from the body.
+    pub callees: Vec<String>,
+}
+
+impl UserFunction {
+    pub fn check_arguments_count(&self, got: usize) -> Result<(), EnvironmentError> {
+        if self.parameters.len() == got {
+            Ok(())
+        } else {
+            Err(EnvironmentError::InvalidArgumentsCount {
+                name: self.name.clone(),
+                expected: self.parameters.len(),
+                got,
+                span: None,
+            })
+        }
+    }
+}
+
+static NEXT_FUNCTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Variables and user functions, which persist between evaluations.
+///
+/// Every variable gets a stable slot in continuous memory block, so JIT compiled code
+/// is able to load variable values by offset.
+#[derive(Clone, Debug)]
+pub struct Environment<N = f64> {
+    slots: HashMap<String, usize>,
+    values: Vec<N>,
+    functions: HashMap<String, Arc<UserFunction>>,
+}
+
+impl Environment {
+    /// Creates environment of `f64` values, other number types are created by `default`.
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+}
+
+impl<N> Default for Environment<N> {
+    fn default() -> Self {
+        Environment {
+            slots: HashMap::new(),
+            values: Vec::new(),
+            functions: HashMap::new(),
+        }
+    }
+}
+
+impl<N: Number> Environment<N> {
+    pub fn slot(&self, name: &str) -> Result<usize, EnvironmentError> {
+        self.slots
+            .get(name)
+            .copied()
+            .ok_or_else(|| EnvironmentError::UndefinedVariable {
+                name: name.to_owned(),
+                suggestion: constants::closest(
+                    name,
+                    self.slots
+                        .keys()
+                        .map(String::as_str)
+                        .chain(constants::CONSTANTS.iter().map(|(constant, _)| *constant)),
+                )
+                .map(ToOwned::to_owned),
+                span: None,
+            })
+    }
+
+    pub fn get(&self, name: &str) -> Result<N, EnvironmentError> {
+        self.slot(name).map(|slot| self.values[slot].clone())
+    }
+
+    pub fn set(&mut self, name: &str, value: N) {
+        match self.slots.get(name) {
+            Some(slot) => self.values[*slot] = value,
+            None => {
+                self.slots.insert(name.to_owned(), self.values.len());
+                self.values.push(value);
+            }
+        }
+    }
+
+    /// Memory block with variable values, indexed by slot.
+    pub fn values(&self) -> &[N] {
+        &self.values
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &N)> {
+        self.slots
+            .iter()
+            .map(move |(name, slot)| (name.as_str(), &self.values[*slot]))
+    }
+
+    pub fn function(&self, name: &str) -> Result<&Arc<UserFunction>, EnvironmentError> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| EnvironmentError::UndefinedFunction {
+                name: name.to_owned(),
+                suggestion: constants::closest(name, self.functions.keys().map(String::as_str))
+                    .map(ToOwned::to_owned),
+                span: None,
+            })
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &Arc<UserFunction>> {
+        self.functions.values()
+    }
+
+    /// Defines or redefines function.
+    ///
+    /// Body may reference only parameters and already defined variables, and may call only
+    /// already defined functions, which don't call the function being defined.
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        parameters: Vec<String>,
+        body: Ast,
+    ) -> Result<(), EnvironmentError> {
+        let mut result = Ok(());
+
+        body.visit(&mut |ast| {
+            if result.is_err() {
+                return;
+            }
+
+            result = match &ast.kind {
+                AstKind::Variable(variable) if !parameters.contains(variable) => {
+                    self.slot(variable).map(|_| ())
+                }
+                AstKind::UserFunctionCall {
+                    name: callee,
+                    arguments,
+                } => {
+                    if callee == name || self.calls(callee, name) {
+                        Err(EnvironmentError::RecursiveFunction {
+                            name: name.to_owned(),
+                            span: None,
+                        })
+                    } else {
+                        self.function(callee)
+                            .and_then(|callee| callee.check_arguments_count(arguments.len()))
+                    }
+                }
+                _ => Ok(()),
+            }
+            .map_err(|error| error.at(ast.span));
+        });
+
+        result?;
+
+        self.functions.insert(
+            name.to_owned(),
+            Arc::new(UserFunction {
+                id: NEXT_FUNCTION_ID.fetch_add(1, Ordering::Relaxed),
+                name: name.to_owned(),
+                parameters,
+                callees: body.user_function_calls(),
+                body,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Checks whether function `caller` directly or indirectly calls function `callee`.
+    fn calls(&self, caller: &str, callee: &str) -> bool {
+        match self.functions.get(caller) {
+            Some(caller) => caller
+                .callees
+                .iter()
+                .any(|name| name == callee || self.calls(name, callee)),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::build_ast;
+
+    #[test]
+    fn test_variables() {
+        let mut environment = Environment::new();
+        environment.set("x", 1.0);
+        environment.set("y", 2.0);
+        environment.set("x", 3.0);
+
+        assert_eq!(Ok(3.0), environment.get("x"));
+        assert_eq!(Ok(1), environment.slot("y"));
+        assert_eq!(&[3.0, 2.0], environment.values());
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut environment = Environment::new();
+        environment.set("c", 1.0);
+
+        let define = |environment: &mut Environment, s: &str| match build_ast(s).unwrap().kind {
+            AstKind::FunctionDefinition {
+                name,
+                parameters,
+                body,
+            } => environment.define_function(&name, parameters, *body),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(Ok(()), define(&mut environment, "f(x) = x + c"));
+        assert_eq!(Ok(()), define(&mut environment, "g(x, y) = f(x) * y"));
+        assert_eq!(
+            vec!["f".to_owned()],
+            environment.function("g").unwrap().callees
+        );
+
+        assert_eq!(
+            Err(EnvironmentError::RecursiveFunction {
+                name: "f".to_owned(),
+                span: Some(Span::new(7, 14))
+            }),
+            define(&mut environment, "f(x) = g(x, 1)")
+        );
+        assert_eq!(
+            Err(EnvironmentError::InvalidArgumentsCount {
+                name: "f".to_owned(),
+                expected: 1,
+                got: 2,
+                span: Some(Span::new(7, 14))
+            }),
+            define(&mut environment, "h(x) = f(x, x)")
+        );
+        assert_eq!(
+            Err(EnvironmentError::UndefinedVariable {
+                name: "z".to_owned(),
+                suggestion: None,
+                span: Some(Span::new(11, 12))
+            }),
+            define(&mut environment, "h(x) = x + z")
+        );
+        assert_eq!(
+            Err(EnvironmentError::UndefinedFunction {
+                name: "ff".to_owned(),
+                suggestion: Some("f".to_owned()),
+                span: Some(Span::new(7, 12))
+            }),
+            define(&mut environment, "h(x) = ff(x)")
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let mut environment = Environment::new();
+        environment.set("velocity", 1.0);
+
+        assert_eq!(
+            Err(EnvironmentError::UndefinedVariable {
+                name: "velocty".to_owned(),
+                suggestion: Some("velocity".to_owned()),
+                span: None
+            }),
+            environment.get("velocty")
+        );
+        assert_eq!(
+            Err(EnvironmentError::UndefinedVariable {
+                name: "b".to_owned(),
+                suggestion: None,
+                span: None
+            }),
+            environment.get("b")
+        );
+    }
+}
@@ -21,52 +21,183 @@
  *
  */
 
+pub use super::environment::Environment;
 use super::interpret::Interpreter;
 use super::jit::Jit;
 pub use super::jit::JitOptimizationLevel;
-use crate::ast::{Ast, AstBuilder};
+use crate::ast::{Ast, AstBuilder, AstKind, Program};
 use crate::errors::Error;
+use crate::number::{Integer, Number};
 use clone_all::clone_all;
-use crossbeam::channel::{bounded, select};
+use crossbeam::channel::{bounded, select, unbounded, Sender};
 use log::*;
+use std::any::Any;
 use std::sync::Arc;
 use std::thread;
 
-pub struct Hybrid {}
+/// Expression, which is sent to the JIT thread.
+enum JitTask {
+    Float {
+        ast: Arc<Ast>,
+        environment: Arc<Environment>,
+        result: Sender<Result<f64, Error>>,
+    },
+    Integer {
+        ast: Arc<Ast>,
+        environment: Arc<Environment<Integer>>,
+        result: Sender<Result<Integer, Error>>,
+    },
+}
 
-impl Hybrid {}
+/// Races interpreter against JIT.
+///
+/// JIT lives in its own long-running thread, so functions compiled by it stay resident between evaluations.
+/// It only compiles `f64` and checked integer arithmetic, so other number types are evaluated by interpreter alone.
+pub struct Hybrid {
+    jit: Sender<JitTask>,
+}
 
 impl Hybrid {
-    pub fn exec(s: &str, optimization_level: JitOptimizationLevel) -> Result<f64, Error> {
-        Hybrid::exec_ast(AstBuilder::build_ast(s)?, optimization_level)
+    pub fn new(optimization_level: JitOptimizationLevel) -> Hybrid {
+        let (jit, tasks) = unbounded::<JitTask>();
+
+        let _ = thread::spawn(move || {
+            let mut jit = Jit::new(optimization_level);
+
+            for task in tasks {
+                match task {
+                    JitTask::Float {
+                        ast,
+                        environment,
+                        result,
+                    } => result.send(jit.exec_expression(&ast, &environment)).ok(),
+                    JitTask::Integer {
+                        ast,
+                        environment,
+                        result,
+                    } => result
+                        .send(jit.exec_integer_expression(&ast, &environment))
+                        .ok(),
+                };
+            }
+        });
+
+        Hybrid { jit }
     }
 
-    pub fn exec_ast(ast: Ast, optimization_level: JitOptimizationLevel) -> Result<f64, Error> {
+    /// Executes program, returning result of its last statement.
+    pub fn exec<N: Number>(
+        &self,
+        s: &str,
+        environment: &mut Environment<N>,
+    ) -> Result<Option<N>, Error> {
+        Ok(self
+            .exec_all(s, environment)?
+            .pop()
+            .and_then(|result| result))
+    }
+
+    /// Executes program, returning results of all statements.
+    pub fn exec_all<N: Number>(
+        &self,
+        s: &str,
+        environment: &mut Environment<N>,
+    ) -> Result<Vec<Option<N>>, Error> {
+        self.exec_program(AstBuilder::build_program(s)?, environment)
+    }
+
+    /// Executes statements one by one, stopping at the first error.
+    pub fn exec_program<N: Number>(
+        &self,
+        program: Program,
+        environment: &mut Environment<N>,
+    ) -> Result<Vec<Option<N>>, Error> {
+        program
+            .statements
+            .into_iter()
+            .map(|statement| self.exec_ast(statement, environment))
+            .collect()
+    }
+
+    pub fn exec_ast<N: Number>(
+        &self,
+        ast: Ast,
+        environment: &mut Environment<N>,
+    ) -> Result<Option<N>, Error> {
+        match ast.kind {
+            AstKind::Assignment { name, value } => {
+                let value = self.evaluate(*value, environment)?;
+                environment.set(&name, value.clone());
+                Ok(Some(value))
+            }
+            AstKind::FunctionDefinition {
+                name,
+                parameters,
+                body,
+            } => {
+                environment.define_function(&name, parameters, *body)?;
+                Ok(None)
+            }
+            kind => self
+                .evaluate(Ast::new(kind, ast.span), environment)
+                .map(Some),
+        }
+    }
+
+    fn evaluate<N: Number>(&self, ast: Ast, environment: &Environment<N>) -> Result<N, Error> {
+        let any = environment as &dyn Any;
+
+        if let Some(environment) = any.downcast_ref::<Environment>() {
+            let result = self.race(ast, environment, |ast, environment, result| {
+                JitTask::Float {
+                    ast,
+                    environment,
+                    result,
+                }
+            })?;
+            Ok((&result as &dyn Any).downcast_ref::<N>().unwrap().clone())
+        } else if let Some(environment) = any.downcast_ref::<Environment<Integer>>() {
+            let result = self.race(ast, environment, |ast, environment, result| {
+                JitTask::Integer {
+                    ast,
+                    environment,
+                    result,
+                }
+            })?;
+            Ok((&result as &dyn Any).downcast_ref::<N>().unwrap().clone())
+        } else {
+            Interpreter::exec_expression(&ast, environment)
+        }
+    }
+
+    /// Evaluates expression simultaneously by interpreter and JIT, returning result of the fastest one.
+    fn race<N: Number>(
+        &self,
+        ast: Ast,
+        environment: &Environment<N>,
+        task: impl FnOnce(Arc<Ast>, Arc<Environment<N>>, Sender<Result<N, Error>>) -> JitTask,
+    ) -> Result<N, Error> {
         debug!("Starting to execute hybrid engine on AST");
 
         let current_time = time::precise_time_s();
 
         let ast = Arc::new(ast);
+        let environment = Arc::new(environment.clone());
 
         let (first_send, first_receive) = bounded(1);
         let (second_send, second_receive) = bounded(1);
 
         let _ = thread::spawn({
-            clone_all!(ast);
+            clone_all!(ast, environment);
             move || {
-                first_send.send(Interpreter::exec_ast(&ast)).ok();
-            }
-        });
-
-        let _ = thread::spawn({
-            clone_all!(ast);
-            move || {
-                second_send
-                    .send(Jit::exec_ast(&ast, optimization_level.into()))
+                first_send
+                    .send(Interpreter::exec_expression(&ast, &environment))
                     .ok();
             }
         });
 
+        self.jit.send(task(ast, environment, second_send)).ok();
+
         let result = select! {
             recv(first_receive) -> result => {
                 debug!("Interpreter won: {:?}", result);
@@ -74,7 +205,11 @@ impl Hybrid {
             },
             recv(second_receive) -> result => {
                 debug!("JIT won: {:?}", result);
-                result.unwrap()
+                // Interpreter is the reference, so it decides, when JIT fails or its thread is gone
+                match result {
+                    Ok(Ok(result)) => Ok(result),
+                    _ => first_receive.recv().unwrap(),
+                }
             }
         };
 
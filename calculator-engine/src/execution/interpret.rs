@@ -21,75 +21,530 @@
  *
  */
 
-use crate::ast::{Ast, AstBuilder};
+use super::environment::Environment;
+use crate::ast::{Ast, AstBuilder, AstKind, Program};
 use crate::errors::Error;
+use crate::number::{Literal, Number, NumberError};
 use crate::parser::Operator;
+use crate::span::Span;
 use log::*;
 use snafu::Snafu;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Values of parameters of the function being called.
+type Locals<'a, N> = HashMap<&'a str, N>;
 
 #[derive(Snafu, Debug, Clone)]
 pub enum InterpreterError {
     #[snafu(display("Invalid unary operator {}", operator))]
-    InvalidUnaryOperator { operator: Operator },
+    InvalidUnaryOperator { operator: Operator, span: Span },
+
+    #[snafu(display("Statement can't be used as expression"))]
+    NestedStatement { span: Span },
+
+    #[snafu(display("Expression contains syntax errors"))]
+    ErrorNode { span: Span },
+
+    #[snafu(display("{}", source))]
+    Arithmetic { source: NumberError, span: Span },
+}
+
+impl InterpreterError {
+    pub fn span(&self) -> Span {
+        match self {
+            InterpreterError::InvalidUnaryOperator { span, .. }
+            | InterpreterError::NestedStatement { span }
+            | InterpreterError::ErrorNode { span }
+            | InterpreterError::Arithmetic { span, .. } => *span,
+        }
+    }
 }
 
 pub struct Interpreter {}
 
 impl Interpreter {
-    pub fn exec_ast(ast: &Ast) -> Result<f64, Error> {
+    /// Executes statement, returning nothing for function definitions.
+    pub fn exec_ast<N: Number>(
+        ast: &Ast,
+        environment: &mut Environment<N>,
+    ) -> Result<Option<N>, Error> {
         debug!(
             "Starting to execute interpretation engine on AST: {:?}",
             ast
         );
 
-        Interpreter::_exec_ast(ast)
+        match &ast.kind {
+            AstKind::Assignment { name, value } => {
+                let value = Interpreter::_exec_ast(value, environment, &Locals::new())?;
+                environment.set(name, value.clone());
+                Ok(Some(value))
+            }
+            AstKind::FunctionDefinition {
+                name,
+                parameters,
+                body,
+            } => {
+                environment.define_function(name, parameters.clone(), (**body).clone())?;
+                Ok(None)
+            }
+            _ => Interpreter::_exec_ast(ast, environment, &Locals::new()).map(Some),
+        }
     }
 
-    fn _exec_ast(ast: &Ast) -> Result<f64, Error> {
-        match ast {
-            Ast::Number(n) => Ok(*n),
-            Ast::UnaryOperator { operator, child } => {
-                let result = Interpreter::_exec_ast(&child)?;
+    /// Evaluates expression, which doesn't modify environment.
+    pub fn exec_expression<N: Number>(ast: &Ast, environment: &Environment<N>) -> Result<N, Error> {
+        debug!(
+            "Starting to execute interpretation engine on expression: {:?}",
+            ast
+        );
+
+        Interpreter::_exec_ast(ast, environment, &Locals::new())
+    }
+
+    fn _exec_ast<N: Number>(
+        ast: &Ast,
+        environment: &Environment<N>,
+        locals: &Locals<N>,
+    ) -> Result<N, Error> {
+        let span = ast.span;
+        let arithmetic = |result: Result<N, NumberError>| {
+            result.map_err(|source| Error::from(InterpreterError::Arithmetic { source, span }))
+        };
+        let truth = |value: &N| {
+            value
+                .is_true()
+                .map_err(|source| Error::from(InterpreterError::Arithmetic { source, span }))
+        };
+
+        match &ast.kind {
+            AstKind::Number(literal) => arithmetic(N::from_literal(literal)),
+            AstKind::Quantity { value, unit } => {
+                // Variable named like unit is multiplied instead, e.g. `t = 2; 3t`
+                let variable = unit.symbol().and_then(|name| match locals.get(name) {
+                    Some(value) => Some(value.clone()),
+                    None => environment.get(name).ok(),
+                });
+
+                arithmetic(N::from_literal(value).and_then(|value| match variable {
+                    Some(variable) => value.mul(&variable),
+                    None => value.with_unit(unit),
+                }))
+            }
+            AstKind::Conversion { child, unit } => {
+                arithmetic(Interpreter::_exec_ast(&child, environment, locals)?.convert(unit))
+            }
+            AstKind::UnaryOperator { operator, child } => {
+                let result = Interpreter::_exec_ast(&child, environment, locals)?;
 
                 match *operator {
                     Operator::Plus => Ok(result),
-                    Operator::Minus => Ok(-result),
-                    operator => Err(InterpreterError::InvalidUnaryOperator { operator }.into()),
+                    Operator::Minus => arithmetic(result.neg()),
+                    Operator::BitNot => arithmetic(result.bit_not()),
+                    Operator::Not => arithmetic(N::from_bool(!truth(&result)?)),
+                    operator => {
+                        Err(InterpreterError::InvalidUnaryOperator { operator, span }.into())
+                    }
                 }
             }
-            Ast::BinaryOperator {
+            AstKind::PostfixOperator { operator, child } => {
+                let result = Interpreter::_exec_ast(&child, environment, locals)?;
+
+                match *operator {
+                    Operator::Factorial => arithmetic(result.factorial()),
+                    operator => {
+                        Err(InterpreterError::InvalidUnaryOperator { operator, span }.into())
+                    }
+                }
+            }
+            AstKind::BinaryOperator {
                 operator,
                 left,
                 right,
+                ..
+            } => {
+                let left = Interpreter::_exec_ast(&left, environment, locals)?;
+
+                // Right operand of logical operator is evaluated only when it decides the result
+                if let Operator::And | Operator::Or = operator {
+                    let decisive = *operator == Operator::Or;
+                    let result = if truth(&left)? == decisive {
+                        decisive
+                    } else {
+                        truth(&Interpreter::_exec_ast(&right, environment, locals)?)?
+                    };
+
+                    return arithmetic(N::from_bool(result));
+                }
+
+                let right = Interpreter::_exec_ast(&right, environment, locals)?;
+
+                arithmetic(match operator {
+                    Operator::Plus => left.add(&right),
+                    Operator::Minus => left.sub(&right),
+                    Operator::Multiply => left.mul(&right),
+                    Operator::Divide => left.div(&right),
+                    Operator::Power => left.pow(&right),
+                    Operator::FloorDivide => left.floor_div(&right),
+                    Operator::Remainder => left.rem(&right),
+                    Operator::BitAnd => left.bit_and(&right),
+                    Operator::BitOr => left.bit_or(&right),
+                    Operator::BitXor => left.bit_xor(&right),
+                    Operator::ShiftLeft => left.shl(&right),
+                    Operator::ShiftRight => left.shr(&right),
+                    Operator::BitNot => unreachable!("`~` is only a prefix operator"),
+                    Operator::Of => unreachable!("`of` is only built as percentage"),
+                    Operator::Factorial => unreachable!("`!` is only a postfix operator"),
+                    Operator::Not => unreachable!("`not` is only a prefix operator"),
+                    Operator::And | Operator::Or => {
+                        unreachable!("logical operators are evaluated above")
+                    }
+                    Operator::Equal
+                    | Operator::NotEqual
+                    | Operator::Less
+                    | Operator::LessEqual
+                    | Operator::Greater
+                    | Operator::GreaterEqual => left
+                        .compare(&right)
+                        .and_then(|ordering| N::from_bool(holds(*operator, ordering))),
+                    Operator::PlusMinus => left
+                        .sub(&right)
+                        .and_then(|lower| N::interval(&lower, &left.add(&right)?)),
+                })
+            }
+            AstKind::Parenthesis { child } => Interpreter::_exec_ast(&child, environment, locals),
+            AstKind::Percent { child } => {
+                let child = Interpreter::_exec_ast(&child, environment, locals)?;
+
+                arithmetic(hundred().and_then(|hundred| child.div(&hundred)))
+            }
+            AstKind::PercentChange {
+                operator,
+                base,
+                percent,
             } => {
-                let left = Interpreter::_exec_ast(&left)?;
-                let right = Interpreter::_exec_ast(&right)?;
-
-                Ok(match operator {
-                    Operator::Plus => left + right,
-                    Operator::Minus => left - right,
-                    Operator::Multiply => left * right,
-                    Operator::Divide => left / right,
+                let base = Interpreter::_exec_ast(&base, environment, locals)?;
+                let percent = Interpreter::_exec_ast(&percent, environment, locals)?;
+                let change = base
+                    .mul(&percent)
+                    .and_then(|product| product.div(&hundred()?));
+
+                arithmetic(match operator {
+                    Operator::Minus => change.and_then(|change| base.sub(&change)),
+                    _ => change.and_then(|change| base.add(&change)),
                 })
             }
-            Ast::Parenthesis { child } => Interpreter::_exec_ast(&child),
+            AstKind::PercentOf { percent, whole } => {
+                let percent = Interpreter::_exec_ast(&percent, environment, locals)?;
+                let whole = Interpreter::_exec_ast(&whole, environment, locals)?;
+
+                arithmetic(
+                    percent
+                        .mul(&whole)
+                        .and_then(|product| product.div(&hundred()?)),
+                )
+            }
+            AstKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let condition = Interpreter::_exec_ast(&condition, environment, locals)?;
+
+                if truth(&condition)? {
+                    Interpreter::_exec_ast(&then, environment, locals)
+                } else {
+                    Interpreter::_exec_ast(&otherwise, environment, locals)
+                }
+            }
+            AstKind::Interval { lower, upper } => {
+                let lower = Interpreter::_exec_ast(&lower, environment, locals)?;
+                let upper = Interpreter::_exec_ast(&upper, environment, locals)?;
+
+                arithmetic(N::interval(&lower, &upper))
+            }
+            AstKind::FunctionCall {
+                function,
+                arguments,
+            } => arithmetic(N::call(
+                *function,
+                &arguments
+                    .iter()
+                    .map(|argument| Interpreter::_exec_ast(argument, environment, locals))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            AstKind::UserFunctionCall { name, arguments } => {
+                let function = environment.function(name).map_err(|error| error.at(span))?;
+                function
+                    .check_arguments_count(arguments.len())
+                    .map_err(|error| error.at(span))?;
+
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| Interpreter::_exec_ast(argument, environment, locals))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let locals = function
+                    .parameters
+                    .iter()
+                    .map(String::as_str)
+                    .zip(arguments)
+                    .collect();
+
+                Interpreter::_exec_ast(&function.body, environment, &locals)
+            }
+            AstKind::Variable(name) => match locals.get(name.as_str()) {
+                Some(value) => Ok(value.clone()),
+                None => Ok(environment.get(name).map_err(|error| error.at(span))?),
+            },
+            AstKind::Assignment { .. } | AstKind::FunctionDefinition { .. } => {
+                Err(InterpreterError::NestedStatement { span }.into())
+            }
+            AstKind::Error => Err(InterpreterError::ErrorNode { span }.into()),
         }
     }
 
-    pub fn exec(s: &str) -> Result<f64, Error> {
+    /// Executes statements one by one, returning result of every statement.
+    /// Execution stops at the first error, keeping changes made by the preceding statements.
+    pub fn exec_program<N: Number>(
+        program: &Program,
+        environment: &mut Environment<N>,
+    ) -> Result<Vec<Option<N>>, Error> {
+        program
+            .statements
+            .iter()
+            .map(|statement| Interpreter::exec_ast(statement, environment))
+            .collect()
+    }
+
+    /// Executes program, returning result of its last statement.
+    pub fn exec<N: Number>(s: &str, environment: &mut Environment<N>) -> Result<Option<N>, Error> {
+        Ok(Interpreter::exec_all(s, environment)?
+            .pop()
+            .and_then(|result| result))
+    }
+
+    /// Executes program, returning results of all statements.
+    pub fn exec_all<N: Number>(
+        s: &str,
+        environment: &mut Environment<N>,
+    ) -> Result<Vec<Option<N>>, Error> {
         debug!("Starting to execute interpretation engine on string: {}", s);
 
-        Interpreter::_exec_ast(&AstBuilder::build_ast(s)?)
+        Interpreter::exec_program(&AstBuilder::build_program(s)?, environment)
+    }
+}
+
+/// Divisor of percentages, which is exact in every number type.
+fn hundred<N: Number>() -> Result<N, NumberError> {
+    N::from_literal(&Literal::Decimal("100".to_owned()))
+}
+
+/// Whether comparison holds for operands with the given order, unordered ones are only unequal.
+fn holds(operator: Operator, ordering: Option<Ordering>) -> bool {
+    match (operator, ordering) {
+        (Operator::NotEqual, ordering) => ordering != Some(Ordering::Equal),
+        (_, None) => false,
+        (Operator::Equal, Some(ordering)) => ordering == Ordering::Equal,
+        (Operator::Less, Some(ordering)) => ordering == Ordering::Less,
+        (Operator::LessEqual, Some(ordering)) => ordering != Ordering::Greater,
+        (Operator::Greater, Some(ordering)) => ordering == Ordering::Greater,
+        (Operator::GreaterEqual, Some(ordering)) => ordering != Ordering::Less,
+        (operator, _) => unreachable!("{} isn't a comparison", operator),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Interpreter;
+    use crate::execution::environment::{Environment, EnvironmentError};
+    use crate::span::Span;
+    use crate::Error;
+
+    fn exec(s: &str, environment: &mut Environment) -> Result<f64, Error> {
+        Interpreter::exec(s, environment).map(Option::unwrap)
+    }
 
     #[test]
     fn test_simple_expression() {
-        assert_eq!(Interpreter::exec("1 + 2").unwrap() as i32, 3);
-        assert_eq!(Interpreter::exec("2 + 2  * 2").unwrap() as i32, 6);
+        assert_eq!(exec("1 + 2", &mut Environment::new()).unwrap() as i32, 3);
+        assert_eq!(
+            exec("2 + 2  * 2", &mut Environment::new()).unwrap() as i32,
+            6
+        );
+    }
+
+    #[test]
+    fn test_power() {
+        assert_eq!(exec("2 ^ 3 ^ 2", &mut Environment::new()).unwrap(), 512.0);
+        assert_eq!(exec("2 ** 3", &mut Environment::new()).unwrap(), 8.0);
+        assert_eq!(exec("-2 ^ 2", &mut Environment::new()).unwrap(), -4.0);
+        assert_eq!(exec("2 ^ -1", &mut Environment::new()).unwrap(), 0.5);
+        assert_eq!(exec("-2 + 3", &mut Environment::new()).unwrap(), 1.0);
+        assert_eq!(exec("2 * 3 ^ 2", &mut Environment::new()).unwrap(), 18.0);
+    }
+
+    #[test]
+    fn test_percent() {
+        let mut environment = Environment::new();
+        assert_eq!(exec("200 + 10%", &mut environment).unwrap(), 220.0);
+        assert_eq!(exec("200 - 10% * 2", &mut environment).unwrap(), 199.8);
+        assert_eq!(exec("50% of 80", &mut environment).unwrap(), 40.0);
+        assert_eq!(exec("25%", &mut environment).unwrap(), 0.25);
+        assert_eq!(exec("7 % 4", &mut environment).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_factorial() {
+        let mut environment = Environment::new();
+        assert_eq!(exec("-3! + 2 ^ 3!", &mut environment).unwrap(), 58.0);
+        assert_eq!(exec("170! / 169!", &mut environment).unwrap(), 170.0);
+        assert!((exec("0.5!", &mut environment).unwrap() - 0.886_226_925_452_758).abs() < 1e-14);
+        assert_eq!(
+            exec("nCr(52, 5) + nPr(5, 2)", &mut environment).unwrap(),
+            2_598_980.0
+        );
+        assert_eq!(exec("17 mod 5", &mut environment).unwrap(), 2.0);
+        assert!(exec("(-1)!", &mut environment).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_conditional() {
+        let mut environment = Environment::new();
+        exec("x = 5", &mut environment).unwrap();
+        exec("y = 2", &mut environment).unwrap();
+        assert_eq!(exec("x > 3 and y <= 2", &mut environment).unwrap(), 1.0);
+        assert_eq!(exec("not x == 5 or y != 2", &mut environment).unwrap(), 0.0);
+        assert_eq!(exec("1 < 2 == true", &mut environment).unwrap(), 1.0);
+        assert_eq!(exec("nan != nan", &mut environment).unwrap(), 1.0);
+        assert_eq!(
+            exec("if x > y then x else y", &mut environment).unwrap(),
+            5.0
+        );
+        assert_eq!(
+            exec("x < 0 ? -1 : x == 0 ? 0 : 1", &mut environment).unwrap(),
+            1.0
+        );
+        // Only the chosen branch and the deciding operands are evaluated
+        assert_eq!(exec("false and unknown", &mut environment).unwrap(), 0.0);
+        assert_eq!(exec("x or unknown", &mut environment).unwrap(), 1.0);
+        assert_eq!(exec("y ? 3 : unknown", &mut environment).unwrap(), 3.0);
+        assert!(exec("true and unknown", &mut environment).is_err());
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        let mut environment = Environment::new();
+        exec("x = 4", &mut environment).unwrap();
+        assert_eq!(exec("2x + 3(4 + 5)", &mut environment).unwrap(), 35.0);
+        assert_eq!(exec("(x)(x - 1) sqrt(x)", &mut environment).unwrap(), 24.0);
+        assert_eq!(exec("1/2x", &mut environment).unwrap(), 2.0);
+        // Variables named like units outside of units mode
+        assert_eq!(exec("t = 2; 3t", &mut environment).unwrap(), 6.0);
+        assert_eq!(
+            exec("m = 1; 4m + 2 min(3, 4)", &mut environment).unwrap(),
+            10.0
+        );
+    }
+
+    #[test]
+    fn test_program() {
+        let mut environment = Environment::new();
+        assert_eq!(
+            exec("a = 2; b = a * 3; b + 1", &mut environment).unwrap(),
+            7.0
+        );
+        assert_eq!(
+            Interpreter::exec_all("f(x) = x ^ 2\n\nf(a);\r\nb = f(b);", &mut environment).unwrap(),
+            vec![None, Some(4.0), Some(36.0)]
+        );
+        assert!(Interpreter::exec(";\n", &mut environment).is_err());
+        // Statements preceding the error are executed
+        assert!(exec("c = 1; c / d", &mut environment).is_err());
+        assert_eq!(exec("c", &mut environment).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_function_call() {
+        assert_eq!(exec("sqrt(16) + 1", &mut Environment::new()).unwrap(), 5.0);
+        assert_eq!(
+            exec("max(1, min(5, 3))", &mut Environment::new()).unwrap(),
+            3.0
+        );
+        assert_eq!(
+            exec("abs(-2) * pow(2, 3)", &mut Environment::new()).unwrap(),
+            16.0
+        );
+        assert_eq!(exec("ln(exp(2))", &mut Environment::new()).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            exec("2 * pi * 6371", &mut Environment::new()).unwrap(),
+            2.0 * std::f64::consts::PI * 6371.0
+        );
+        assert_eq!(
+            exec("tau / 2", &mut Environment::new()).unwrap(),
+            std::f64::consts::PI
+        );
+        assert_eq!(
+            exec("-inf", &mut Environment::new()).unwrap(),
+            std::f64::NEG_INFINITY
+        );
+        assert!(exec("nan + 1", &mut Environment::new()).unwrap().is_nan());
+
+        // Real numbers have no imaginary unit, so `i` is a variable
+        let mut environment = Environment::new();
+        assert_eq!(
+            exec("i = 3; f(i) = i ^ 2; f(i)", &mut environment).unwrap(),
+            9.0
+        );
+        assert!(exec("2i", &mut environment).is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut environment = Environment::new();
+
+        assert_eq!(exec("x = 3 * 4", &mut environment).unwrap(), 12.0);
+        assert_eq!(exec("x / 2", &mut environment).unwrap(), 6.0);
+        assert_eq!(exec("x = x + 1", &mut environment).unwrap(), 13.0);
+        assert_eq!(environment.get("x"), Ok(13.0));
+
+        match exec("2 * y + 1", &mut environment).unwrap_err() {
+            Error::EnvironmentError(EnvironmentError::UndefinedVariable { name, span, .. }) => {
+                assert_eq!((name.as_str(), span), ("y", Some(Span::new(4, 5))))
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_user_functions() {
+        let mut environment = Environment::new();
+
+        assert_eq!(
+            Interpreter::exec("f(x, y) = x^2 + y", &mut environment).unwrap(),
+            None
+        );
+        assert_eq!(exec("f(3, 1)", &mut environment).unwrap(), 10.0);
+
+        exec("c = 10", &mut environment).unwrap();
+        Interpreter::exec("g(x) = f(x, c) / 2", &mut environment).unwrap();
+        assert_eq!(exec("g(2)", &mut environment).unwrap(), 7.0);
+
+        exec("c = 20", &mut environment).unwrap();
+        Interpreter::exec("f(x, y) = x + y", &mut environment).unwrap();
+        assert_eq!(exec("g(2)", &mut environment).unwrap(), 11.0);
+
+        match exec("f(1)", &mut environment).unwrap_err() {
+            Error::EnvironmentError(EnvironmentError::InvalidArgumentsCount {
+                expected,
+                got,
+                ..
+            }) => assert_eq!((expected, got), (2, 1)),
+            e => panic!("Unexpected error {:?}", e),
+        }
     }
 }
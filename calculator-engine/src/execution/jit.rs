@@ -21,22 +21,34 @@
  *
  */
 
-use crate::ast::{Ast, AstBuilder};
+use super::environment::Environment;
+use crate::ast::{Ast, AstBuilder, AstKind, Program};
 use crate::errors::Error;
+use crate::functions::Function;
+use crate::number::{Integer, NumberError};
 use crate::parser::Operator;
+use crate::span::Span;
 use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(feature = "llvm_jit")] {
         use inkwell::{
             builder::Builder, context::Context, execution_engine::ExecutionEngine,
-            execution_engine::JitFunction, types::FloatType, values::FloatValue, OptimizationLevel,
+            execution_engine::JitFunction, module::Module, types::FloatType,
+            types::FunctionType, types::IntType, values::BasicValueEnum, values::FloatValue,
+            values::FunctionValue, values::IntValue, values::PointerValue, AddressSpace,
+            FloatPredicate, OptimizationLevel,
         };
         use derive_more::Constructor;
+        use std::cell::RefCell;
+        use std::collections::HashMap;
     } else if #[cfg(feature = "cranelift_jit")] {
         use cranelift::prelude::*;
-        use cranelift_module::{Linkage, Module};
+        use cranelift_module::{FuncId, Linkage, Module};
+        use cranelift::codegen::ir::FuncRef;
         use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+        use crate::number::Number;
+        use std::collections::HashMap;
     }
 }
 
@@ -72,24 +84,137 @@ impl Default for JitOptimizationLevel {
 #[derive(Snafu, Debug)]
 pub enum JitError {
     #[snafu(display("JIT engine doesn't support unary operator: {}", operator))]
-    UnsupportedUnaryOperator { operator: Operator },
+    UnsupportedUnaryOperator { operator: Operator, span: Span },
+
+    #[snafu(display("JIT engine doesn't support expression: {}", expression))]
+    UnsupportedExpression { expression: String, span: Span },
+
+    #[snafu(display("Statement can't be used as expression"))]
+    NestedStatement { span: Span },
+
+    #[snafu(display("Expression contains syntax errors"))]
+    ErrorNode { span: Span },
+
+    /// Checked integer operation failed, either at compile time or by trapping in compiled code.
+    #[snafu(display("{}", source))]
+    Arithmetic { source: NumberError, span: Span },
 }
 
-pub struct Jit {}
+/// Error for expressions, which only interpreter is able to evaluate.
+fn unsupported(ast: &Ast) -> Error {
+    JitError::UnsupportedExpression {
+        expression: ast.to_string(),
+        span: ast.span,
+    }
+    .into()
+}
 
-type JitFunc = unsafe extern "C" fn() -> f64;
+impl JitError {
+    pub fn span(&self) -> Span {
+        match self {
+            JitError::UnsupportedUnaryOperator { span, .. }
+            | JitError::UnsupportedExpression { span, .. }
+            | JitError::NestedStatement { span }
+            | JitError::ErrorNode { span }
+            | JitError::Arithmetic { span, .. } => *span,
+        }
+    }
+}
+
+/// JIT compiler session.
+///
+/// With Cranelift backend user functions are compiled once into the resident module,
+/// which lives as long as the session, while every expression is compiled into its own module.
+pub struct Jit {
+    optimization_level: JitOptimizationLevel,
+    #[cfg(feature = "cranelift_jit")]
+    module: Module<SimpleJITBackend>,
+    /// Compiled user functions, keyed by function id and ids of compiled callees,
+    /// so function is recompiled when any of its callees is redefined.
+    #[cfg(feature = "cranelift_jit")]
+    functions: HashMap<(u64, Vec<FuncId>), CompiledFunction>,
+}
+
+#[cfg(feature = "cranelift_jit")]
+#[derive(Clone)]
+struct CompiledFunction {
+    id: FuncId,
+    symbol: String,
+    address: *const u8,
+    arity: usize,
+}
+
+/// Compiled expression, which receives memory block with variable values.
+type JitFunc = unsafe extern "C" fn(*const f64) -> f64;
+
+/// Compiled integer expression, which receives memory block with variable values and
+/// location, where number of the failed check is stored.
+#[cfg(feature = "cranelift_jit")]
+type IntegerJitFunc = unsafe extern "C" fn(*const Integer, *mut i64) -> i64;
 
 impl Jit {
-    pub fn exec(s: &str, optimization_level: JitOptimizationLevel) -> Result<f64, Error> {
+    pub fn new(optimization_level: JitOptimizationLevel) -> Jit {
+        Jit {
+            optimization_level,
+            #[cfg(feature = "cranelift_jit")]
+            module: Jit::create_module(&[]),
+            #[cfg(feature = "cranelift_jit")]
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Executes program, returning result of its last statement.
+    pub fn exec(&mut self, s: &str, environment: &mut Environment) -> Result<Option<f64>, Error> {
         debug!("Starting to execute JIT engine on string: {}", s);
 
-        Jit::exec_ast(&AstBuilder::build_ast(s)?, optimization_level)
+        Ok(self
+            .exec_program(&AstBuilder::build_program(s)?, environment)?
+            .pop()
+            .and_then(|result| result))
+    }
+
+    /// Executes statements one by one, returning result of every statement.
+    pub fn exec_program(
+        &mut self,
+        program: &Program,
+        environment: &mut Environment,
+    ) -> Result<Vec<Option<f64>>, Error> {
+        program
+            .statements
+            .iter()
+            .map(|statement| self.exec_ast(statement, environment))
+            .collect()
+    }
+
+    pub fn exec_ast(
+        &mut self,
+        ast: &Ast,
+        environment: &mut Environment,
+    ) -> Result<Option<f64>, Error> {
+        match &ast.kind {
+            AstKind::Assignment { name, value } => {
+                let value = self.exec_expression(value, environment)?;
+                environment.set(name, value);
+                Ok(Some(value))
+            }
+            AstKind::FunctionDefinition {
+                name,
+                parameters,
+                body,
+            } => {
+                environment.define_function(name, parameters.clone(), (**body).clone())?;
+                Ok(None)
+            }
+            _ => self.exec_expression(ast, environment).map(Some),
+        }
     }
 
     #[cfg(feature = "llvm_jit")]
-    pub fn exec_ast(ast: &Ast, optimization_level: JitOptimizationLevel) -> Result<f64, Error> {
+    pub fn exec_expression(&mut self, ast: &Ast, environment: &Environment) -> Result<f64, Error> {
         debug!("Starting to execute JIT engine on AST: {:?}", ast);
 
+        check_user_function_calls(ast, environment)?;
+
         ExecutionEngine::link_in_mc_jit();
 
         let context = Context::create();
@@ -98,42 +223,298 @@ impl Jit {
         let builder = context.create_builder();
 
         let execution_engine = module
-            .create_jit_execution_engine(optimization_level.into())
+            .create_jit_execution_engine(self.optimization_level.into())
             .unwrap();
 
-        let f64_type = context.f64_type();
-        let fn_type = f64_type.fn_type(&[], false);
-
-        let function = module.add_function("exec", fn_type, None);
-        let basic_block = context.append_basic_block(function.clone(), "entry");
-
-        builder.position_at_end(&basic_block);
+        /// Values available in the body of function being built.
+        struct Scope<'a, 'b> {
+            variables: PointerValue<'a>,
+            parameters: HashMap<&'b str, FloatValue<'a>>,
+        }
 
         #[derive(Constructor)]
         struct RecursiveBuilder<'a> {
+            context: &'a Context,
             f64_type: FloatType<'a>,
+            i64_type: IntType<'a>,
             builder: &'a Builder<'a>,
+            module: &'a Module<'a>,
+            execution_engine: &'a ExecutionEngine<'a>,
+            environment: &'a Environment,
+            user_functions: RefCell<HashMap<String, FunctionValue<'a>>>,
         }
 
         impl<'a> RecursiveBuilder<'a> {
-            pub fn build(&self, ast: &Ast) -> FloatValue {
-                match ast {
-                    Ast::Number(n) => self.f64_type.const_float(*n),
-                    Ast::UnaryOperator { operator, child } => {
-                        let child = self.build(&child);
-                        match operator {
-                            Operator::Minus => child.const_neg(),
+            /// Type of compiled function, which receives arguments and memory block with variable values.
+            fn function_type(&self, arity: usize) -> FunctionType<'a> {
+                let mut parameters = vec![self.f64_type.into(); arity];
+                parameters.push(self.f64_type.ptr_type(AddressSpace::Generic).into());
+
+                self.f64_type.fn_type(&parameters, false)
+            }
+
+            fn build_function(
+                &self,
+                name: &str,
+                parameters: &[String],
+                body: &Ast,
+            ) -> Result<FunctionValue<'a>, Error> {
+                for callee in body.user_function_calls() {
+                    self.build_user_function(&callee)?;
+                }
+
+                let function =
+                    self.module
+                        .add_function(name, self.function_type(parameters.len()), None);
+                let basic_block = self.context.append_basic_block(function.clone(), "entry");
+
+                self.builder.position_at_end(&basic_block);
+
+                let values = function.get_params();
+                let (variables, values) = values.split_last().unwrap();
+
+                let scope = Scope {
+                    variables: variables.into_pointer_value(),
+                    parameters: parameters
+                        .iter()
+                        .map(String::as_str)
+                        .zip(values.iter().map(|value| value.into_float_value()))
+                        .collect(),
+                };
+
+                let return_value = self.build(body, &scope)?;
+                self.builder.build_return(Some(&return_value));
+
+                Ok(function)
+            }
+
+            fn build_user_function(&self, name: &str) -> Result<FunctionValue<'a>, Error> {
+                if let Some(function) = self.user_functions.borrow().get(name) {
+                    return Ok(function.clone());
+                }
+
+                let function = self.environment.function(name)?;
+                let function_value = self.build_function(
+                    &format!("user_{}", name),
+                    &function.parameters,
+                    &function.body,
+                )?;
+
+                self.user_functions
+                    .borrow_mut()
+                    .insert(name.to_owned(), function_value.clone());
+
+                Ok(function_value)
+            }
+
+            /// Declares built-in function in the module and maps it to runtime implementation.
+            fn declare_function(&self, function: Function) -> FunctionValue<'a> {
+                self.module
+                    .get_function(function.symbol())
+                    .unwrap_or_else(|| {
+                        let function_value = self.module.add_function(
+                            function.symbol(),
+                            self.f64_type
+                                .fn_type(&vec![self.f64_type.into(); function.arity()], false),
+                            None,
+                        );
+                        self.execution_engine
+                            .add_global_mapping(&function_value, function.address() as usize);
+                        function_value
+                    })
+            }
+
+            fn build_call(
+                &self,
+                function: FunctionValue<'a>,
+                arguments: &[BasicValueEnum<'a>],
+            ) -> FloatValue<'a> {
+                self.builder
+                    .build_call(function, arguments, "call_temp")
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_float_value()
+            }
+
+            fn build_variable(
+                &self,
+                name: &str,
+                span: Span,
+                scope: &Scope<'a, '_>,
+            ) -> Result<FloatValue<'a>, Error> {
+                if let Some(value) = scope.parameters.get(name) {
+                    return Ok(*value);
+                }
+
+                let slot = self
+                    .environment
+                    .slot(name)
+                    .map_err(|error| error.at(span))?;
+
+                let pointer = unsafe {
+                    self.builder.build_in_bounds_gep(
+                        scope.variables,
+                        &[self.i64_type.const_int(slot as u64, false)],
+                        "variable_pointer",
+                    )
+                };
+
+                Ok(self.builder.build_load(pointer, name).into_float_value())
+            }
+
+            fn build_arguments(
+                &self,
+                arguments: &[Ast],
+                scope: &Scope<'a, '_>,
+            ) -> Result<Vec<BasicValueEnum<'a>>, Error> {
+                arguments
+                    .iter()
+                    .map(|argument| self.build(argument, scope).map(Into::into))
+                    .collect()
+            }
+
+            /// Every value except zero is true, including NaN.
+            fn build_truth(&self, value: FloatValue<'a>) -> IntValue<'a> {
+                self.builder.build_float_compare(
+                    FloatPredicate::UNE,
+                    value,
+                    self.f64_type.const_zero(),
+                    "truth_temp",
+                )
+            }
+
+            /// Converts boolean into number `1` or `0`.
+            fn build_bool(&self, value: IntValue<'a>) -> FloatValue<'a> {
+                self.builder
+                    .build_unsigned_int_to_float(value, self.f64_type, "bool_temp")
+            }
+
+            fn build_comparison(
+                &self,
+                predicate: FloatPredicate,
+                left: FloatValue<'a>,
+                right: FloatValue<'a>,
+            ) -> FloatValue<'a> {
+                self.build_bool(self.builder.build_float_compare(
+                    predicate,
+                    left,
+                    right,
+                    "compare_temp",
+                ))
+            }
+
+            /// Builds blocks, which evaluate only one of the values depending on condition,
+            /// and merges their results.
+            fn build_branch(
+                &self,
+                condition: IntValue<'a>,
+                then: impl FnOnce() -> Result<FloatValue<'a>, Error>,
+                otherwise: impl FnOnce() -> Result<FloatValue<'a>, Error>,
+            ) -> Result<FloatValue<'a>, Error> {
+                let function = self
+                    .builder
+                    .get_insert_block()
+                    .and_then(|block| block.get_parent())
+                    .unwrap();
+                let then_block = self.context.append_basic_block(function.clone(), "then");
+                let else_block = self.context.append_basic_block(function.clone(), "else");
+                let merge_block = self.context.append_basic_block(function, "merge");
+
+                self.builder
+                    .build_conditional_branch(condition, &then_block, &else_block);
+
+                // Nested branches move the end of the branch into another block
+                self.builder.position_at_end(&then_block);
+                let then_value = then()?;
+                self.builder.build_unconditional_branch(&merge_block);
+                let then_block = self.builder.get_insert_block().unwrap();
+
+                self.builder.position_at_end(&else_block);
+                let else_value = otherwise()?;
+                self.builder.build_unconditional_branch(&merge_block);
+                let else_block = self.builder.get_insert_block().unwrap();
+
+                self.builder.position_at_end(&merge_block);
+                let phi = self.builder.build_phi(self.f64_type, "branch_temp");
+                phi.add_incoming(&[(&then_value, &then_block), (&else_value, &else_block)]);
+
+                Ok(phi.as_basic_value().into_float_value())
+            }
+
+            pub fn build(&self, ast: &Ast, scope: &Scope<'a, '_>) -> Result<FloatValue<'a>, Error> {
+                let span = ast.span;
+
+                Ok(match &ast.kind {
+                    AstKind::Number(literal) => self.f64_type.const_float(literal.to_f64()),
+                    AstKind::UnaryOperator { operator, child } => {
+                        let child = self.build(&child, scope)?;
+                        match *operator {
+                            Operator::Minus => self.builder.build_float_neg(child, "negate_temp"),
                             Operator::Plus => child,
-                            _ => unreachable!(),
+                            Operator::Not => self.build_comparison(
+                                FloatPredicate::OEQ,
+                                child,
+                                self.f64_type.const_zero(),
+                            ),
+                            operator => {
+                                return Err(
+                                    JitError::UnsupportedUnaryOperator { operator, span }.into()
+                                )
+                            }
                         }
                     }
-                    Ast::BinaryOperator {
+                    AstKind::PostfixOperator { operator, child } => {
+                        let child = self.build(&child, scope)?;
+                        match *operator {
+                            // Runtime helper computes factorial as `gamma(x + 1)`
+                            Operator::Factorial => self.build_call(
+                                self.declare_function(Function::Gamma),
+                                &[self
+                                    .builder
+                                    .build_float_add(
+                                        child,
+                                        self.f64_type.const_float(1.0),
+                                        "plus_temp",
+                                    )
+                                    .into()],
+                            ),
+                            operator => {
+                                return Err(
+                                    JitError::UnsupportedUnaryOperator { operator, span }.into()
+                                )
+                            }
+                        }
+                    }
+                    AstKind::BinaryOperator {
                         operator,
                         left,
                         right,
+                        ..
                     } => {
-                        let left = self.build(&left);
-                        let right = self.build(&right);
+                        let left = self.build(&left, scope)?;
+
+                        // Right operand of logical operator is evaluated only when it decides the result
+                        if let Operator::And | Operator::Or = operator {
+                            let truth = self.build_truth(left);
+                            let right_truth = || {
+                                let right = self.build(&right, scope)?;
+                                Ok(self.build_bool(self.build_truth(right)))
+                            };
+
+                            return match operator {
+                                Operator::And => self.build_branch(truth, right_truth, || {
+                                    Ok(self.f64_type.const_zero())
+                                }),
+                                _ => self.build_branch(
+                                    truth,
+                                    || Ok(self.f64_type.const_float(1.0)),
+                                    right_truth,
+                                ),
+                            };
+                        }
+
+                        let right = self.build(&right, scope)?;
 
                         match operator {
                             Operator::Plus => {
@@ -148,85 +529,236 @@ impl Jit {
                             Operator::Multiply => {
                                 self.builder.build_float_mul(left, right, "multiply_temp")
                             }
+                            Operator::Power => self.build_call(
+                                self.declare_function(Function::Pow),
+                                &[left.into(), right.into()],
+                            ),
+                            Operator::FloorDivide | Operator::Remainder => {
+                                let quotient = self.build_call(
+                                    self.declare_function(Function::Floor),
+                                    &[self
+                                        .builder
+                                        .build_float_div(left, right, "divide_temp")
+                                        .into()],
+                                );
+                                match operator {
+                                    Operator::FloorDivide => quotient,
+                                    _ => self.builder.build_float_sub(
+                                        left,
+                                        self.builder.build_float_mul(
+                                            right,
+                                            quotient,
+                                            "multiply_temp",
+                                        ),
+                                        "remainder_temp",
+                                    ),
+                                }
+                            }
+                            Operator::Equal => {
+                                self.build_comparison(FloatPredicate::OEQ, left, right)
+                            }
+                            Operator::NotEqual => {
+                                self.build_comparison(FloatPredicate::UNE, left, right)
+                            }
+                            Operator::Less => {
+                                self.build_comparison(FloatPredicate::OLT, left, right)
+                            }
+                            Operator::LessEqual => {
+                                self.build_comparison(FloatPredicate::OLE, left, right)
+                            }
+                            Operator::Greater => {
+                                self.build_comparison(FloatPredicate::OGT, left, right)
+                            }
+                            Operator::GreaterEqual => {
+                                self.build_comparison(FloatPredicate::OGE, left, right)
+                            }
+                            Operator::And | Operator::Or => {
+                                unreachable!("logical operators are built above")
+                            }
+                            Operator::PlusMinus
+                            | Operator::BitAnd
+                            | Operator::BitOr
+                            | Operator::BitXor
+                            | Operator::BitNot
+                            | Operator::Not
+                            | Operator::Of
+                            | Operator::Factorial
+                            | Operator::ShiftLeft
+                            | Operator::ShiftRight => return Err(unsupported(ast)),
                         }
                     }
-                    Ast::Parenthesis { child } => self.build(&child),
-                }
+                    AstKind::Conditional {
+                        condition,
+                        then,
+                        otherwise,
+                    } => {
+                        let condition = self.build(&condition, scope)?;
+
+                        self.build_branch(
+                            self.build_truth(condition),
+                            || self.build(&then, scope),
+                            || self.build(&otherwise, scope),
+                        )?
+                    }
+                    AstKind::Parenthesis { child } => self.build(&child, scope)?,
+                    AstKind::Percent { child } => self.builder.build_float_div(
+                        self.build(&child, scope)?,
+                        self.f64_type.const_float(100.0),
+                        "percent_temp",
+                    ),
+                    AstKind::PercentChange {
+                        operator,
+                        base,
+                        percent,
+                    } => {
+                        let base = self.build(&base, scope)?;
+                        let percent = self.build(&percent, scope)?;
+                        let change = self.builder.build_float_div(
+                            self.builder.build_float_mul(base, percent, "multiply_temp"),
+                            self.f64_type.const_float(100.0),
+                            "percent_temp",
+                        );
+
+                        match operator {
+                            Operator::Minus => {
+                                self.builder.build_float_sub(base, change, "minus_temp")
+                            }
+                            _ => self.builder.build_float_add(base, change, "plus_temp"),
+                        }
+                    }
+                    AstKind::PercentOf { percent, whole } => self.builder.build_float_div(
+                        self.builder.build_float_mul(
+                            self.build(&percent, scope)?,
+                            self.build(&whole, scope)?,
+                            "multiply_temp",
+                        ),
+                        self.f64_type.const_float(100.0),
+                        "percent_temp",
+                    ),
+                    AstKind::Interval { .. }
+                    | AstKind::Quantity { .. }
+                    | AstKind::Conversion { .. } => return Err(unsupported(ast)),
+                    AstKind::FunctionCall {
+                        function,
+                        arguments,
+                    } => self.build_call(
+                        self.declare_function(*function),
+                        &self.build_arguments(arguments, scope)?,
+                    ),
+                    AstKind::UserFunctionCall { name, arguments } => {
+                        self.environment
+                            .function(name)
+                            .and_then(|function| function.check_arguments_count(arguments.len()))
+                            .map_err(|error| error.at(span))?;
+
+                        let mut arguments = self.build_arguments(arguments, scope)?;
+                        arguments.push(scope.variables.into());
+
+                        self.build_call(self.build_user_function(name)?, &arguments)
+                    }
+                    AstKind::Variable(name) => self.build_variable(name, span, scope)?,
+                    AstKind::Assignment { .. } | AstKind::FunctionDefinition { .. } => {
+                        return Err(JitError::NestedStatement { span }.into())
+                    }
+                    AstKind::Error => return Err(JitError::ErrorNode { span }.into()),
+                })
             }
         }
 
-        let recursive_builder = RecursiveBuilder::new(f64_type, &builder);
-        let return_value = recursive_builder.build(ast);
-        builder.build_return(Some(&return_value));
+        let recursive_builder = RecursiveBuilder::new(
+            &context,
+            context.f64_type(),
+            context.i64_type(),
+            &builder,
+            &module,
+            &execution_engine,
+            environment,
+            RefCell::new(HashMap::new()),
+        );
+        recursive_builder.build_function("exec", &[], ast)?;
 
         debug!(
             "Generated LLVM IR: {}",
-            function.print_to_string().to_string()
+            module.print_to_string().to_string()
         );
 
         unsafe {
             let jit_function: JitFunction<JitFunc> = execution_engine.get_function("exec").unwrap();
 
-            Ok(jit_function.call())
+            Ok(jit_function.call(environment.values().as_ptr()))
         }
     }
 
+    /// LLVM backend compiles only `f64` arithmetic.
+    #[cfg(feature = "llvm_jit")]
+    pub fn exec_integer_expression(
+        &mut self,
+        ast: &Ast,
+        _environment: &Environment<Integer>,
+    ) -> Result<Integer, Error> {
+        Err(unsupported(ast))
+    }
+
+    /// Evaluates expression over checked integers.
+    ///
+    /// Every check branches to the trap block, which stores number of the check and returns,
+    /// so the failure is reported as error with span of the sub-expression instead of a signal.
     #[cfg(feature = "cranelift_jit")]
-    pub fn exec_ast(ast: &Ast, _: JitOptimizationLevel) -> Result<f64, Error> {
-        let builder = SimpleJITBuilder::new(cranelift_module::default_libcall_names());
+    pub fn exec_integer_expression(
+        &mut self,
+        ast: &Ast,
+        environment: &Environment<Integer>,
+    ) -> Result<Integer, Error> {
+        debug!("Starting to execute integer JIT engine on AST: {:?}", ast);
+
+        let mut module = Jit::create_module(&[]);
         let mut builder_context = FunctionBuilderContext::new();
-        let mut module: Module<SimpleJITBackend> = Module::new(builder);
         let mut context = module.make_context();
 
-        context
-            .func
-            .signature
-            .returns
-            .push(AbiParam::new(types::F64));
+        let pointer_type = module.target_config().pointer_type();
+        let signature = &mut context.func.signature;
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.returns.push(AbiParam::new(types::I64));
 
         let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
         let entry_ebb = builder.create_ebb();
 
+        builder.append_ebb_params_for_function_params(entry_ebb);
         builder.switch_to_block(entry_ebb);
         builder.seal_block(entry_ebb);
 
-        fn build(builder: &mut FunctionBuilder<'_>, ast: &Ast) -> Value {
-            match ast {
-                Ast::Number(n) => builder.ins().f64const(*n),
-                Ast::UnaryOperator { operator, child } => {
-                    let child = build(builder, &child);
-                    match operator {
-                        Operator::Minus => builder.ins().fneg(child),
-                        Operator::Plus => child,
-                        _ => unreachable!(),
-                    }
-                }
-                Ast::BinaryOperator {
-                    operator,
-                    left,
-                    right,
-                } => {
-                    let left = build(builder, &left);
-                    let right = build(builder, &right);
+        let (variables, failed_check) = (
+            builder.ebb_params(entry_ebb)[0],
+            builder.ebb_params(entry_ebb)[1],
+        );
 
-                    match operator {
-                        Operator::Plus => builder.ins().fadd(left, right),
-                        Operator::Minus => builder.ins().fsub(left, right),
-                        Operator::Divide => builder.ins().fdiv(left, right),
-                        Operator::Multiply => builder.ins().fmul(left, right),
-                    }
-                }
-                Ast::Parenthesis { child } => build(builder, &child),
-            }
-        }
-        let return_value = build(&mut builder, ast);
+        let trap_ebb = builder.create_ebb();
+        builder.append_ebb_param(trap_ebb, types::I64);
+
+        let mut integer_builder = IntegerBuilder {
+            builder,
+            environment,
+            variables,
+            trap_ebb,
+            checks: Vec::new(),
+        };
+        let return_value = integer_builder.build(ast)?;
+        let IntegerBuilder {
+            mut builder,
+            checks,
+            ..
+        } = integer_builder;
+
+        let _ = builder.ins().return_(&[return_value]);
 
-        let return_variable = Variable::new(0);
-        builder.declare_var(return_variable, types::F64);
-        builder.def_var(return_variable, return_value);
+        builder.switch_to_block(trap_ebb);
+        builder.seal_block(trap_ebb);
 
-        let return_var = [builder.use_var(return_variable)];
-        let _ = builder.ins().return_(&return_var);
+        let check = builder.ebb_params(trap_ebb)[0];
+        builder.ins().store(MemFlags::new(), check, failed_check, 0);
+        let zero = builder.ins().iconst(types::I64, 0);
+        let _ = builder.ins().return_(&[zero]);
 
         builder.finalize();
 
@@ -237,9 +769,859 @@ impl Jit {
         module.clear_context(&mut context);
         module.finalize_definitions();
 
+        // Checks are numbered from one, so zero means success
+        let mut failed_check = 0;
+        let result = unsafe {
+            let function: IntegerJitFunc =
+                std::mem::transmute(module.get_finalized_function(function_id));
+            (function)(environment.values().as_ptr(), &mut failed_check)
+        };
+
+        match failed_check {
+            0 => Ok(Integer(result)),
+            check => {
+                let (source, span) = checks[check as usize - 1].clone();
+                Err(JitError::Arithmetic { source, span }.into())
+            }
+        }
+    }
+
+    #[cfg(feature = "cranelift_jit")]
+    fn create_module(symbols: &[(String, *const u8)]) -> Module<SimpleJITBackend> {
+        let mut builder = SimpleJITBuilder::new(cranelift_module::default_libcall_names());
+        builder.symbols(
+            Function::ALL
+                .iter()
+                .map(|function| (function.symbol(), function.address())),
+        );
+        builder.symbols(symbols.iter().cloned());
+
+        Module::new(builder)
+    }
+
+    /// Compiles user function with its callees into the resident module, unless it's already compiled.
+    #[cfg(feature = "cranelift_jit")]
+    fn compile_function(
+        &mut self,
+        name: &str,
+        environment: &Environment,
+    ) -> Result<CompiledFunction, Error> {
+        let function = environment.function(name)?.clone();
+
+        let mut callees = HashMap::new();
+        for callee in &function.callees {
+            callees.insert(
+                callee.clone(),
+                self.compile_function(callee, environment)?.id,
+            );
+        }
+
+        let key = (
+            function.id,
+            function
+                .callees
+                .iter()
+                .map(|callee| callees[callee])
+                .collect::<Vec<_>>(),
+        );
+
+        if let Some(compiled) = self.functions.get(&key) {
+            return Ok(compiled.clone());
+        }
+
+        let symbol = format!("user_{}_{}", function.name, self.functions.len());
+
+        debug!("Compiling user function {} as {}", function.name, symbol);
+
+        let id = define_function(
+            &mut self.module,
+            &symbol,
+            Linkage::Local,
+            &function.parameters,
+            &function.body,
+            environment,
+            &callees,
+        )?;
+        self.module.finalize_definitions();
+
+        let compiled = CompiledFunction {
+            id,
+            address: self.module.get_finalized_function(id),
+            symbol,
+            arity: function.parameters.len(),
+        };
+        self.functions.insert(key, compiled.clone());
+
+        Ok(compiled)
+    }
+
+    #[cfg(feature = "cranelift_jit")]
+    pub fn exec_expression(&mut self, ast: &Ast, environment: &Environment) -> Result<f64, Error> {
+        debug!("Starting to execute JIT engine on AST: {:?}", ast);
+
+        check_user_function_calls(ast, environment)?;
+
+        let mut imports = HashMap::new();
+        for name in ast.user_function_calls() {
+            let compiled = self.compile_function(&name, environment)?;
+            imports.insert(name, compiled);
+        }
+
+        let mut module = Jit::create_module(
+            &imports
+                .values()
+                .map(|compiled| (compiled.symbol.clone(), compiled.address))
+                .collect::<Vec<_>>(),
+        );
+
+        let callees = imports
+            .into_iter()
+            .map(|(name, compiled)| {
+                let signature = function_signature(&module, compiled.arity);
+                let id = module
+                    .declare_function(&compiled.symbol, Linkage::Import, &signature)
+                    .unwrap();
+                (name, id)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let function_id = define_function(
+            &mut module,
+            "exec",
+            Linkage::Export,
+            &[],
+            ast,
+            environment,
+            &callees,
+        )?;
+        module.finalize_definitions();
+
         unsafe {
             let function: JitFunc = std::mem::transmute(module.get_finalized_function(function_id));
-            Ok((function)())
+            Ok((function)(environment.values().as_ptr()))
+        }
+    }
+}
+
+/// Checks that every user function called in the tree is defined and receives right number of
+/// arguments, so errors point to the call instead of the function being compiled.
+fn check_user_function_calls(ast: &Ast, environment: &Environment) -> Result<(), Error> {
+    let mut result = Ok(());
+
+    ast.visit(&mut |node| {
+        if let AstKind::UserFunctionCall { name, arguments } = &node.kind {
+            if result.is_ok() {
+                result = environment
+                    .function(name)
+                    .and_then(|function| function.check_arguments_count(arguments.len()))
+                    .map_err(|error| error.at(node.span));
+            }
+        }
+    });
+
+    Ok(result?)
+}
+
+/// Signature of compiled function, which receives arguments and memory block with variable values.
+#[cfg(feature = "cranelift_jit")]
+fn function_signature(module: &Module<SimpleJITBackend>, arity: usize) -> Signature {
+    let mut signature = module.make_signature();
+    for _ in 0..arity {
+        signature.params.push(AbiParam::new(types::F64));
+    }
+    signature
+        .params
+        .push(AbiParam::new(module.target_config().pointer_type()));
+    signature.returns.push(AbiParam::new(types::F64));
+
+    signature
+}
+
+#[cfg(feature = "cranelift_jit")]
+fn define_function(
+    module: &mut Module<SimpleJITBackend>,
+    symbol: &str,
+    linkage: Linkage,
+    parameters: &[String],
+    body: &Ast,
+    environment: &Environment,
+    callees: &HashMap<String, FuncId>,
+) -> Result<FuncId, Error> {
+    let mut builder_context = FunctionBuilderContext::new();
+    let mut context = module.make_context();
+    context.func.signature = function_signature(module, parameters.len());
+
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+    let entry_ebb = builder.create_ebb();
+
+    builder.append_ebb_params_for_function_params(entry_ebb);
+    builder.switch_to_block(entry_ebb);
+    builder.seal_block(entry_ebb);
+
+    let values = builder.ebb_params(entry_ebb).to_vec();
+    let (variables, values) = values.split_last().unwrap();
+
+    let mut recursive_builder = RecursiveBuilder {
+        builder,
+        module,
+        environment,
+        variables: *variables,
+        parameters: parameters
+            .iter()
+            .map(String::as_str)
+            .zip(values.iter().copied())
+            .collect(),
+        callees,
+    };
+    let return_value = recursive_builder.build(body)?;
+    let mut builder = recursive_builder.builder;
+
+    let return_variable = Variable::new(0);
+    builder.declare_var(return_variable, types::F64);
+    builder.def_var(return_variable, return_value);
+
+    let return_var = [builder.use_var(return_variable)];
+    let _ = builder.ins().return_(&return_var);
+
+    builder.finalize();
+
+    let function_id = module
+        .declare_function(symbol, linkage, &context.func.signature)
+        .unwrap();
+    module.define_function(function_id, &mut context).unwrap();
+    module.clear_context(&mut context);
+
+    Ok(function_id)
+}
+
+/// Control flow, which is shared by builders of `f64` and integer expressions.
+#[cfg(feature = "cranelift_jit")]
+trait BranchBuilder<'a>: Sized {
+    /// Type of the built values.
+    const TYPE: Type;
+
+    fn function_builder(&mut self) -> &mut FunctionBuilder<'a>;
+
+    fn build(&mut self, ast: &Ast) -> Result<Value, Error>;
+
+    /// Condition for branching, which is false only for zero.
+    fn build_condition(&mut self, value: Value) -> Value;
+
+    /// Converts truth of value into number `1` or `0`.
+    fn build_boolean(&mut self, value: Value) -> Value;
+
+    fn build_constant(&mut self, value: i64) -> Value;
+
+    /// Builds blocks, which evaluate only one of the values depending on condition,
+    /// and passes the result to the merge block as its parameter.
+    fn build_branch(
+        &mut self,
+        condition: Value,
+        then: impl FnOnce(&mut Self) -> Result<Value, Error>,
+        otherwise: impl FnOnce(&mut Self) -> Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        let builder = self.function_builder();
+        let then_ebb = builder.create_ebb();
+        let else_ebb = builder.create_ebb();
+        let merge_ebb = builder.create_ebb();
+        let result = builder.append_ebb_param(merge_ebb, Self::TYPE);
+
+        builder.ins().brz(condition, else_ebb, &[]);
+        builder.ins().jump(then_ebb, &[]);
+
+        builder.switch_to_block(then_ebb);
+        builder.seal_block(then_ebb);
+        let value = then(self)?;
+        self.function_builder().ins().jump(merge_ebb, &[value]);
+
+        let builder = self.function_builder();
+        builder.switch_to_block(else_ebb);
+        builder.seal_block(else_ebb);
+        let value = otherwise(self)?;
+        self.function_builder().ins().jump(merge_ebb, &[value]);
+
+        let builder = self.function_builder();
+        builder.switch_to_block(merge_ebb);
+        builder.seal_block(merge_ebb);
+
+        Ok(result)
+    }
+
+    /// Builds `and` and `or`, whose right operand is evaluated only when it decides the result.
+    fn build_logical(
+        &mut self,
+        operator: Operator,
+        left: Value,
+        right: &Ast,
+    ) -> Result<Value, Error> {
+        let condition = self.build_condition(left);
+        let right_truth = |this: &mut Self| {
+            let right = this.build(right)?;
+            Ok(this.build_boolean(right))
+        };
+
+        match operator {
+            Operator::And => {
+                self.build_branch(condition, right_truth, |this| Ok(this.build_constant(0)))
+            }
+            _ => self.build_branch(condition, |this| Ok(this.build_constant(1)), right_truth),
+        }
+    }
+
+    /// Builds `if c then a else b`, which evaluates only the chosen value.
+    fn build_conditional(
+        &mut self,
+        condition: &Ast,
+        then: &Ast,
+        otherwise: &Ast,
+    ) -> Result<Value, Error> {
+        let condition = self.build(condition)?;
+        let condition = self.build_condition(condition);
+
+        self.build_branch(
+            condition,
+            |this| this.build(then),
+            |this| this.build(otherwise),
+        )
+    }
+}
+
+#[cfg(feature = "cranelift_jit")]
+struct RecursiveBuilder<'a, 'b> {
+    builder: FunctionBuilder<'a>,
+    module: &'b mut Module<SimpleJITBackend>,
+    environment: &'b Environment,
+    /// Memory block with variable values.
+    variables: Value,
+    /// Parameters of the function being built.
+    parameters: HashMap<&'b str, Value>,
+    /// User functions, declared in the module being built.
+    callees: &'b HashMap<String, FuncId>,
+}
+
+#[cfg(feature = "cranelift_jit")]
+impl<'a, 'b> RecursiveBuilder<'a, 'b> {
+    fn declare_function(&mut self, function: Function) -> FuncRef {
+        let mut signature = self.module.make_signature();
+        for _ in 0..function.arity() {
+            signature.params.push(AbiParam::new(types::F64));
+        }
+        signature.returns.push(AbiParam::new(types::F64));
+
+        let function_id = self
+            .module
+            .declare_function(function.symbol(), Linkage::Import, &signature)
+            .unwrap();
+        self.module
+            .declare_func_in_func(function_id, self.builder.func)
+    }
+
+    fn build_call(&mut self, function_ref: FuncRef, arguments: &[Value]) -> Value {
+        let call = self.builder.ins().call(function_ref, arguments);
+        self.builder.inst_results(call)[0]
+    }
+
+    fn build_variable(&mut self, name: &str, span: Span) -> Result<Value, Error> {
+        if let Some(value) = self.parameters.get(name) {
+            return Ok(*value);
+        }
+
+        let slot = self
+            .environment
+            .slot(name)
+            .map_err(|error| error.at(span))?;
+
+        Ok(self.builder.ins().load(
+            types::F64,
+            MemFlags::new(),
+            self.variables,
+            (slot * std::mem::size_of::<f64>()) as i32,
+        ))
+    }
+
+    fn build_arguments(&mut self, arguments: &[Ast]) -> Result<Vec<Value>, Error> {
+        arguments
+            .iter()
+            .map(|argument| self.build(argument))
+            .collect()
+    }
+
+    /// Converts boolean into number `1` or `0`.
+    fn build_bool(&mut self, value: Value) -> Value {
+        let one = self.builder.ins().f64const(1.0);
+        let zero = self.builder.ins().f64const(0.0);
+        self.builder.ins().select(value, one, zero)
+    }
+
+    fn build_comparison(&mut self, condition: FloatCC, left: Value, right: Value) -> Value {
+        let comparison = self.builder.ins().fcmp(condition, left, right);
+        self.build_bool(comparison)
+    }
+}
+
+#[cfg(feature = "cranelift_jit")]
+impl<'a, 'b> BranchBuilder<'a> for RecursiveBuilder<'a, 'b> {
+    const TYPE: Type = types::F64;
+
+    fn function_builder(&mut self) -> &mut FunctionBuilder<'a> {
+        &mut self.builder
+    }
+
+    /// Every value except zero is true, including NaN.
+    fn build_condition(&mut self, value: Value) -> Value {
+        let zero = self.builder.ins().f64const(0.0);
+        self.builder.ins().fcmp(FloatCC::NotEqual, value, zero)
+    }
+
+    fn build_boolean(&mut self, value: Value) -> Value {
+        let truth = self.build_condition(value);
+        self.build_bool(truth)
+    }
+
+    fn build_constant(&mut self, value: i64) -> Value {
+        self.builder.ins().f64const(value as f64)
+    }
+
+    fn build(&mut self, ast: &Ast) -> Result<Value, Error> {
+        let span = ast.span;
+
+        Ok(match &ast.kind {
+            AstKind::Number(literal) => self.builder.ins().f64const(literal.to_f64()),
+            AstKind::UnaryOperator { operator, child } => {
+                let child = self.build(&child)?;
+                match *operator {
+                    Operator::Minus => self.builder.ins().fneg(child),
+                    Operator::Plus => child,
+                    Operator::Not => {
+                        let zero = self.builder.ins().f64const(0.0);
+                        self.build_comparison(FloatCC::Equal, child, zero)
+                    }
+                    operator => {
+                        return Err(JitError::UnsupportedUnaryOperator { operator, span }.into())
+                    }
+                }
+            }
+            AstKind::PostfixOperator { operator, child } => {
+                let child = self.build(&child)?;
+                match *operator {
+                    // Runtime helper computes factorial as `gamma(x + 1)`
+                    Operator::Factorial => {
+                        let one = self.builder.ins().f64const(1.0);
+                        let argument = self.builder.ins().fadd(child, one);
+                        let function_ref = self.declare_function(Function::Gamma);
+                        self.build_call(function_ref, &[argument])
+                    }
+                    operator => {
+                        return Err(JitError::UnsupportedUnaryOperator { operator, span }.into())
+                    }
+                }
+            }
+            AstKind::BinaryOperator {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let left = self.build(&left)?;
+                if let Operator::And | Operator::Or = operator {
+                    return self.build_logical(*operator, left, &right);
+                }
+
+                let right = self.build(&right)?;
+
+                match operator {
+                    Operator::Plus => self.builder.ins().fadd(left, right),
+                    Operator::Minus => self.builder.ins().fsub(left, right),
+                    Operator::Divide => self.builder.ins().fdiv(left, right),
+                    Operator::Multiply => self.builder.ins().fmul(left, right),
+                    Operator::Power => {
+                        let function_ref = self.declare_function(Function::Pow);
+                        self.build_call(function_ref, &[left, right])
+                    }
+                    Operator::FloorDivide => {
+                        let quotient = self.builder.ins().fdiv(left, right);
+                        self.builder.ins().floor(quotient)
+                    }
+                    Operator::Remainder => {
+                        let quotient = self.builder.ins().fdiv(left, right);
+                        let quotient = self.builder.ins().floor(quotient);
+                        let product = self.builder.ins().fmul(right, quotient);
+                        self.builder.ins().fsub(left, product)
+                    }
+                    Operator::Equal => self.build_comparison(FloatCC::Equal, left, right),
+                    Operator::NotEqual => self.build_comparison(FloatCC::NotEqual, left, right),
+                    Operator::Less => self.build_comparison(FloatCC::LessThan, left, right),
+                    Operator::LessEqual => {
+                        self.build_comparison(FloatCC::LessThanOrEqual, left, right)
+                    }
+                    Operator::Greater => self.build_comparison(FloatCC::GreaterThan, left, right),
+                    Operator::GreaterEqual => {
+                        self.build_comparison(FloatCC::GreaterThanOrEqual, left, right)
+                    }
+                    Operator::And | Operator::Or => {
+                        unreachable!("logical operators are built above")
+                    }
+                    Operator::PlusMinus
+                    | Operator::BitAnd
+                    | Operator::BitOr
+                    | Operator::BitXor
+                    | Operator::BitNot
+                    | Operator::Not
+                    | Operator::Of
+                    | Operator::Factorial
+                    | Operator::ShiftLeft
+                    | Operator::ShiftRight => return Err(unsupported(ast)),
+                }
+            }
+            AstKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => self.build_conditional(&condition, &then, &otherwise)?,
+            AstKind::Parenthesis { child } => self.build(&child)?,
+            AstKind::Percent { child } => {
+                let child = self.build(&child)?;
+                let hundred = self.builder.ins().f64const(100.0);
+                self.builder.ins().fdiv(child, hundred)
+            }
+            AstKind::PercentChange {
+                operator,
+                base,
+                percent,
+            } => {
+                let base = self.build(&base)?;
+                let percent = self.build(&percent)?;
+                let product = self.builder.ins().fmul(base, percent);
+                let hundred = self.builder.ins().f64const(100.0);
+                let change = self.builder.ins().fdiv(product, hundred);
+
+                match operator {
+                    Operator::Minus => self.builder.ins().fsub(base, change),
+                    _ => self.builder.ins().fadd(base, change),
+                }
+            }
+            AstKind::PercentOf { percent, whole } => {
+                let percent = self.build(&percent)?;
+                let whole = self.build(&whole)?;
+                let product = self.builder.ins().fmul(percent, whole);
+                let hundred = self.builder.ins().f64const(100.0);
+                self.builder.ins().fdiv(product, hundred)
+            }
+            AstKind::Interval { .. } | AstKind::Quantity { .. } | AstKind::Conversion { .. } => {
+                return Err(unsupported(ast))
+            }
+            AstKind::FunctionCall {
+                function,
+                arguments,
+            } => {
+                let arguments = self.build_arguments(arguments)?;
+                let function_ref = self.declare_function(*function);
+                self.build_call(function_ref, &arguments)
+            }
+            AstKind::UserFunctionCall { name, arguments } => {
+                self.environment
+                    .function(name)
+                    .and_then(|function| function.check_arguments_count(arguments.len()))
+                    .map_err(|error| error.at(span))?;
+
+                let mut arguments = self.build_arguments(arguments)?;
+                arguments.push(self.variables);
+
+                let function_ref = self
+                    .module
+                    .declare_func_in_func(self.callees[name], self.builder.func);
+                self.build_call(function_ref, &arguments)
+            }
+            AstKind::Variable(name) => self.build_variable(name, span)?,
+            AstKind::Assignment { .. } | AstKind::FunctionDefinition { .. } => {
+                return Err(JitError::NestedStatement { span }.into())
+            }
+            AstKind::Error => return Err(JitError::ErrorNode { span }.into()),
+        })
+    }
+}
+
+/// Builder of checked integer expression, which branches to the trap block on failure.
+#[cfg(feature = "cranelift_jit")]
+struct IntegerBuilder<'a, 'b> {
+    builder: FunctionBuilder<'a>,
+    environment: &'b Environment<Integer>,
+    /// Memory block with variable values.
+    variables: Value,
+    /// Block, which receives number of the failed check.
+    trap_ebb: Ebb,
+    /// Error and span of every check, indexed by its number minus one.
+    checks: Vec<(NumberError, Span)>,
+}
+
+#[cfg(feature = "cranelift_jit")]
+impl<'a, 'b> IntegerBuilder<'a, 'b> {
+    /// Registers check, returning its number, which is passed to the trap block.
+    ///
+    /// Number is materialized before the checked instruction, so it doesn't clobber CPU flags.
+    fn check(&mut self, source: NumberError, span: Span) -> Value {
+        self.checks.push((source, span));
+        self.builder
+            .ins()
+            .iconst(types::I64, self.checks.len() as i64)
+    }
+
+    fn overflow(&mut self, ast: &Ast) -> Value {
+        self.check(
+            NumberError::Overflow {
+                operation: ast.to_string(),
+            },
+            ast.span,
+        )
+    }
+
+    /// Negates value, trapping on `i64::MIN`.
+    fn build_neg(&mut self, ast: &Ast, value: Value) -> Value {
+        let check = self.overflow(ast);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        let (result, flags) = self.builder.ins().isub_ifbout(zero, value);
+        self.builder
+            .ins()
+            .brif(IntCC::Overflow, flags, self.trap_ebb, &[check]);
+        result
+    }
+
+    /// Builds `/`, `//` and `%`, trapping on zero divisor and on `i64::MIN / -1`.
+    fn build_division(
+        &mut self,
+        ast: &Ast,
+        operator: Operator,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        let check = self.check(NumberError::DivisionByZero, ast.span);
+        self.builder.ins().brz(right, self.trap_ebb, &[check]);
+
+        let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, right, -1);
+
+        if operator == Operator::Remainder {
+            // Remainder of division by -1 is zero, but `i64::MIN % -1` traps in hardware
+            let one = self.builder.ins().iconst(types::I64, 1);
+            let right = self.builder.ins().select(is_minus_one, one, right);
+            let remainder = self.builder.ins().srem(left, right);
+            let adjust = self.build_floor_adjustment(remainder, right);
+            let zero = self.builder.ins().iconst(types::I64, 0);
+            let addend = self.builder.ins().select(adjust, right, zero);
+            return self.builder.ins().iadd(remainder, addend);
+        }
+
+        let check = self.overflow(ast);
+        let is_min = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::Equal, left, i64::min_value());
+        let overflow = self.builder.ins().band(is_min, is_minus_one);
+        self.builder.ins().brnz(overflow, self.trap_ebb, &[check]);
+
+        let quotient = self.builder.ins().sdiv(left, right);
+        if operator == Operator::Divide {
+            return quotient;
         }
+
+        let remainder = self.builder.ins().srem(left, right);
+        let adjust = self.build_floor_adjustment(remainder, right);
+        let adjust = self.builder.ins().bint(types::I64, adjust);
+        self.builder.ins().isub(quotient, adjust)
+    }
+
+    /// Whether truncated division differs from floor division, i.e. remainder is nonzero and
+    /// its sign differs from the divisor.
+    fn build_floor_adjustment(&mut self, remainder: Value, divisor: Value) -> Value {
+        let is_nonzero = self.builder.ins().icmp_imm(IntCC::NotEqual, remainder, 0);
+        let signs = self.builder.ins().bxor(remainder, divisor);
+        let signs_differ = self.builder.ins().icmp_imm(IntCC::SignedLessThan, signs, 0);
+        self.builder.ins().band(is_nonzero, signs_differ)
+    }
+
+    fn build_variable(&mut self, name: &str, span: Span) -> Result<Value, Error> {
+        let slot = self
+            .environment
+            .slot(name)
+            .map_err(|error| error.at(span))?;
+
+        Ok(self.builder.ins().load(
+            types::I64,
+            MemFlags::new(),
+            self.variables,
+            (slot * std::mem::size_of::<Integer>()) as i32,
+        ))
+    }
+
+    fn build_comparison(&mut self, condition: IntCC, left: Value, right: Value) -> Value {
+        let comparison = self.builder.ins().icmp(condition, left, right);
+        self.builder.ins().bint(types::I64, comparison)
+    }
+}
+
+#[cfg(feature = "cranelift_jit")]
+impl<'a, 'b> BranchBuilder<'a> for IntegerBuilder<'a, 'b> {
+    const TYPE: Type = types::I64;
+
+    fn function_builder(&mut self) -> &mut FunctionBuilder<'a> {
+        &mut self.builder
+    }
+
+    /// Integer is the condition itself, since branching tests it for zero.
+    fn build_condition(&mut self, value: Value) -> Value {
+        value
+    }
+
+    /// Every value except zero is true, which is converted into `1`.
+    fn build_boolean(&mut self, value: Value) -> Value {
+        let truth = self.builder.ins().icmp_imm(IntCC::NotEqual, value, 0);
+        self.builder.ins().bint(types::I64, truth)
+    }
+
+    fn build_constant(&mut self, value: i64) -> Value {
+        self.builder.ins().iconst(types::I64, value)
+    }
+
+    fn build(&mut self, ast: &Ast) -> Result<Value, Error> {
+        let span = ast.span;
+
+        Ok(match &ast.kind {
+            AstKind::Number(literal) => {
+                let value = Integer::from_literal(literal)
+                    .map_err(|source| JitError::Arithmetic { source, span })?;
+                self.builder.ins().iconst(types::I64, value.0)
+            }
+            AstKind::UnaryOperator { operator, child } => {
+                let child = self.build(&child)?;
+                match *operator {
+                    Operator::Minus => self.build_neg(ast, child),
+                    Operator::Plus => child,
+                    Operator::BitNot => self.builder.ins().bnot(child),
+                    Operator::Not => {
+                        let zero = self.builder.ins().iconst(types::I64, 0);
+                        self.build_comparison(IntCC::Equal, child, zero)
+                    }
+                    operator => {
+                        return Err(JitError::UnsupportedUnaryOperator { operator, span }.into())
+                    }
+                }
+            }
+            AstKind::BinaryOperator {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let left = self.build(&left)?;
+                if let Operator::And | Operator::Or = operator {
+                    return self.build_logical(*operator, left, &right);
+                }
+
+                let right = self.build(&right)?;
+
+                match operator {
+                    Operator::Plus => {
+                        let check = self.overflow(ast);
+                        let (result, flags) = self.builder.ins().iadd_ifcout(left, right);
+                        self.builder
+                            .ins()
+                            .brif(IntCC::Overflow, flags, self.trap_ebb, &[check]);
+                        result
+                    }
+                    Operator::Minus => {
+                        let check = self.overflow(ast);
+                        let (result, flags) = self.builder.ins().isub_ifbout(left, right);
+                        self.builder
+                            .ins()
+                            .brif(IntCC::Overflow, flags, self.trap_ebb, &[check]);
+                        result
+                    }
+                    Operator::Multiply => {
+                        // Product fits, when high half is sign extension of the low one
+                        let check = self.overflow(ast);
+                        let result = self.builder.ins().imul(left, right);
+                        let high = self.builder.ins().smulhi(left, right);
+                        let sign = self.builder.ins().sshr_imm(result, 63);
+                        let overflow = self.builder.ins().icmp(IntCC::NotEqual, high, sign);
+                        self.builder.ins().brnz(overflow, self.trap_ebb, &[check]);
+                        result
+                    }
+                    Operator::Divide | Operator::FloorDivide | Operator::Remainder => {
+                        self.build_division(ast, *operator, left, right)
+                    }
+                    Operator::BitAnd => self.builder.ins().band(left, right),
+                    Operator::BitOr => self.builder.ins().bor(left, right),
+                    Operator::BitXor => self.builder.ins().bxor(left, right),
+                    Operator::Equal => self.build_comparison(IntCC::Equal, left, right),
+                    Operator::NotEqual => self.build_comparison(IntCC::NotEqual, left, right),
+                    Operator::Less => self.build_comparison(IntCC::SignedLessThan, left, right),
+                    Operator::LessEqual => {
+                        self.build_comparison(IntCC::SignedLessThanOrEqual, left, right)
+                    }
+                    Operator::Greater => {
+                        self.build_comparison(IntCC::SignedGreaterThan, left, right)
+                    }
+                    Operator::GreaterEqual => {
+                        self.build_comparison(IntCC::SignedGreaterThanOrEqual, left, right)
+                    }
+                    Operator::And | Operator::Or => {
+                        unreachable!("logical operators are built above")
+                    }
+                    Operator::Power
+                    | Operator::PlusMinus
+                    | Operator::BitNot
+                    | Operator::Not
+                    | Operator::Of
+                    | Operator::Factorial
+                    | Operator::ShiftLeft
+                    | Operator::ShiftRight => return Err(unsupported(ast)),
+                }
+            }
+            AstKind::Parenthesis { child } => self.build(&child)?,
+            AstKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => self.build_conditional(&condition, &then, &otherwise)?,
+            AstKind::FunctionCall {
+                function,
+                arguments,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.build(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match (function, arguments.as_slice()) {
+                    (Function::Abs, [x]) => {
+                        let negated = self.build_neg(ast, *x);
+                        let is_negative = self.builder.ins().icmp_imm(IntCC::SignedLessThan, *x, 0);
+                        self.builder.ins().select(is_negative, negated, *x)
+                    }
+                    (Function::Min, [x, y]) => {
+                        let is_less = self.builder.ins().icmp(IntCC::SignedLessThan, *x, *y);
+                        self.builder.ins().select(is_less, *x, *y)
+                    }
+                    (Function::Max, [x, y]) => {
+                        let is_less = self.builder.ins().icmp(IntCC::SignedLessThan, *x, *y);
+                        self.builder.ins().select(is_less, *y, *x)
+                    }
+                    (Function::Floor, [x]) | (Function::Ceil, [x]) | (Function::Round, [x]) => *x,
+                    _ => return Err(unsupported(ast)),
+                }
+            }
+            AstKind::Variable(name) => self.build_variable(name, span)?,
+            AstKind::Interval { .. }
+            | AstKind::Quantity { .. }
+            | AstKind::Conversion { .. }
+            | AstKind::PostfixOperator { .. }
+            | AstKind::Percent { .. }
+            | AstKind::PercentChange { .. }
+            | AstKind::PercentOf { .. }
+            | AstKind::UserFunctionCall { .. } => return Err(unsupported(ast)),
+            AstKind::Assignment { .. } | AstKind::FunctionDefinition { .. } => {
+                return Err(JitError::NestedStatement { span }.into())
+            }
+            AstKind::Error => return Err(JitError::ErrorNode { span }.into()),
+        })
     }
 }
@@ -21,6 +21,7 @@
  *
  */
 
+pub mod environment;
 pub mod hybrid;
 pub mod interpret;
 pub mod jit;
@@ -0,0 +1,388 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Notations of results, e.g. `1.23e4` or `12.3k`.
+
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// How numbers of results are written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Notation {
+    /// Shortest text, which is parsed back to the same value, or fraction in exact mode.
+    Shortest,
+    /// Given number of digits after the point.
+    Fixed(usize),
+    /// Given number of significant digits, switching to scientific notation for large and small values.
+    Significant(usize),
+    /// One digit before the point, e.g. `1.23e4`, with optional number of significant digits.
+    Scientific(Option<usize>),
+    /// Exponent is a multiple of three, e.g. `12.3e3`.
+    Engineering(Option<usize>),
+    /// Engineering notation with SI prefix instead of exponent, e.g. `12.3k`.
+    SiPrefix(Option<usize>),
+}
+
+/// Largest number of digits of `f64` results, which is about its precision.
+pub const MAX_DIGITS: usize = 17;
+
+/// Largest number of digits of exact and decimal results, which are computed to any precision.
+pub const MAX_EXACT_DIGITS: usize = 10_000;
+
+/// Prefixes of exponents from `-24` to `24`.
+const SI_PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+/// Decimal number split into significant digits and exponent of the first one,
+/// e.g. `-0.0123` is `-`, `123` and `-2`.
+#[derive(Clone, Debug, PartialEq)]
+struct Digits {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl Digits {
+    /// Parses decimal number like `-12.30` or `1.23e-4`, which is written by numeric types.
+    fn parse(text: &str) -> Digits {
+        let negative = text.starts_with('-');
+        let text = text.trim_start_matches(&['-', '+'][..]);
+        let mut parts = text.splitn(2, &['e', 'E'][..]);
+        let mantissa = parts.next().unwrap_or_default();
+        let exponent: i64 = parts.next().and_then(|e| e.parse().ok()).unwrap_or(0);
+        let mut mantissa = mantissa.splitn(2, '.');
+        let integer = mantissa.next().unwrap_or_default();
+        let fraction = mantissa.next().unwrap_or_default();
+
+        let all = format!("{}{}", integer, fraction);
+        let digits = all.trim_start_matches('0');
+        let leading_zeros = (all.len() - digits.len()) as i64;
+
+        if digits.is_empty() {
+            return Digits {
+                negative,
+                digits: "0".to_owned(),
+                exponent: 0,
+            };
+        }
+
+        Digits {
+            negative,
+            digits: digits.to_owned(),
+            exponent: exponent + integer.len() as i64 - 1 - leading_zeros,
+        }
+    }
+
+    /// Rounds half away from zero or pads with zeros to exactly `count` significant digits.
+    fn round(mut self, count: usize) -> Digits {
+        let count = count.max(1);
+
+        if self.digits.len() <= count {
+            self.digits
+                .extend(std::iter::repeat('0').take(count - self.digits.len()));
+            return self;
+        }
+
+        let round_up = self.digits.as_bytes()[count] >= b'5';
+        self.digits.truncate(count);
+
+        if round_up {
+            let kept = self.digits.trim_end_matches('9').len();
+            if kept == 0 {
+                // All nines carry into a new digit, e.g. `99.9` becomes `100`
+                self.digits = format!("1{}", "0".repeat(count - 1));
+                self.exponent += 1;
+            } else {
+                let increased = (self.digits.as_bytes()[kept - 1] + 1) as char;
+                self.digits = format!(
+                    "{}{}{}",
+                    &self.digits[..kept - 1],
+                    increased,
+                    "0".repeat(count - kept)
+                );
+            }
+        }
+
+        self
+    }
+
+    fn sign(&self) -> &'static str {
+        if self.negative {
+            "-"
+        } else {
+            ""
+        }
+    }
+
+    /// Digits with the point after `integer` digits, padding them with zeros.
+    fn mantissa(&self, integer: usize) -> String {
+        let mut digits = self.digits.clone();
+        digits.extend(std::iter::repeat('0').take(integer.saturating_sub(digits.len())));
+
+        if digits.len() > integer {
+            format!("{}.{}", &digits[..integer], &digits[integer..])
+        } else {
+            digits
+        }
+    }
+
+    fn plain(&self) -> String {
+        if self.exponent >= 0 {
+            format!(
+                "{}{}",
+                self.sign(),
+                self.mantissa(self.exponent as usize + 1)
+            )
+        } else {
+            let zeros = "0".repeat((-self.exponent - 1) as usize);
+            format!("{}0.{}{}", self.sign(), zeros, self.digits)
+        }
+    }
+
+    fn scientific(&self) -> String {
+        format!("{}{}e{}", self.sign(), self.mantissa(1), self.exponent)
+    }
+
+    /// Mantissa and exponent, which is a multiple of three.
+    fn engineering(&self) -> (String, i64) {
+        let shift = ((self.exponent % 3) + 3) % 3;
+        let mantissa = format!("{}{}", self.sign(), self.mantissa(shift as usize + 1));
+
+        (mantissa, self.exponent - shift)
+    }
+}
+
+impl Notation {
+    /// Number of digits, when the notation is given one.
+    pub fn digits(self) -> Option<usize> {
+        match self {
+            Notation::Fixed(digits) => Some(digits),
+            notation => notation.significant(),
+        }
+    }
+
+    /// Rejects notations with more than `max` digits, e.g. `fixed:30` for `f64` results.
+    pub fn check_digits(self, max: usize) -> Result<Notation, String> {
+        match self.digits() {
+            Some(digits) if digits > max => Err(format!(
+                "Notation {} has {} digits, expected at most {}",
+                self, digits, max
+            )),
+            _ => Ok(self),
+        }
+    }
+
+    /// Number of significant digits, which the notation rounds to.
+    fn significant(self) -> Option<usize> {
+        match self {
+            Notation::Shortest | Notation::Fixed(_) => None,
+            Notation::Significant(digits) => Some(digits),
+            Notation::Scientific(digits)
+            | Notation::Engineering(digits)
+            | Notation::SiPrefix(digits) => digits,
+        }
+    }
+
+    pub fn format_f64(self, value: f64) -> String {
+        match self {
+            Notation::Shortest => value.to_string(),
+            Notation::Fixed(digits) => format!("{:.*}", digits, value),
+            _ if !value.is_finite() => value.to_string(),
+            _ => self.write(Digits::parse(&match self.significant() {
+                Some(digits) => format!("{:.*e}", digits.max(1) - 1, value),
+                None => format!("{:e}", value),
+            })),
+        }
+    }
+
+    /// Writes decimal number like `-12.30`, keeping all of its digits.
+    /// Shortest and fixed notations write it as is, since numeric types implement them themselves.
+    pub fn format_decimal(self, text: &str) -> String {
+        match self {
+            Notation::Shortest | Notation::Fixed(_) => text.to_owned(),
+            _ => self.write(Digits::parse(text)),
+        }
+    }
+
+    fn write(self, digits: Digits) -> String {
+        let digits = match self.significant() {
+            Some(count) => digits.round(count),
+            None => digits,
+        };
+
+        match self {
+            Notation::Shortest | Notation::Fixed(_) => digits.plain(),
+            Notation::Significant(count) => {
+                // Like `toPrecision` in JavaScript
+                if digits.exponent < -6 || digits.exponent >= count.max(1) as i64 {
+                    digits.scientific()
+                } else {
+                    digits.plain()
+                }
+            }
+            Notation::Scientific(_) => digits.scientific(),
+            Notation::Engineering(_) => {
+                let (mantissa, exponent) = digits.engineering();
+                format!("{}e{}", mantissa, exponent)
+            }
+            Notation::SiPrefix(_) => {
+                let (mantissa, exponent) = digits.engineering();
+                match SI_PREFIXES.get(((exponent + 24) / 3) as usize) {
+                    Some(prefix) if (-24..=24).contains(&exponent) => {
+                        format!("{}{}", mantissa, prefix)
+                    }
+                    _ => format!("{}e{}", mantissa, exponent),
+                }
+            }
+        }
+    }
+}
+
+impl Default for Notation {
+    fn default() -> Self {
+        Notation::Shortest
+    }
+}
+
+/// Parses names like `fixed:4` or `scientific`, where digits are optional unless required.
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let digits = match parts.next().map(str::parse::<usize>) {
+            Some(Ok(digits)) if (digits > 0 || name == "fixed") && digits <= MAX_EXACT_DIGITS => {
+                Some(digits)
+            }
+            Some(_) => {
+                return Err(format!(
+                    "Invalid number of digits in notation {}, expected at most {}",
+                    s, MAX_EXACT_DIGITS
+                ))
+            }
+            None => None,
+        };
+
+        match (name, digits) {
+            ("shortest", None) => Ok(Notation::Shortest),
+            ("fixed", Some(digits)) => Ok(Notation::Fixed(digits)),
+            ("significant", Some(digits)) => Ok(Notation::Significant(digits)),
+            ("scientific", digits) => Ok(Notation::Scientific(digits)),
+            ("engineering", digits) => Ok(Notation::Engineering(digits)),
+            ("si", digits) => Ok(Notation::SiPrefix(digits)),
+            _ => Err(format!(
+                "Unknown notation {}, expected shortest, fixed:N, significant:N, \
+                 scientific[:N], engineering[:N] or si[:N]",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Notation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let (name, digits) = match *self {
+            Notation::Shortest => ("shortest", None),
+            Notation::Fixed(digits) => ("fixed", Some(digits)),
+            Notation::Significant(digits) => ("significant", Some(digits)),
+            Notation::Scientific(digits) => ("scientific", digits),
+            Notation::Engineering(digits) => ("engineering", digits),
+            Notation::SiPrefix(digits) => ("si", digits),
+        };
+
+        match digits {
+            Some(digits) => write!(f, "{}:{}", name, digits),
+            None => write!(f, "{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digits() {
+        let digits = |text| {
+            let digits = Digits::parse(text);
+            (digits.digits, digits.exponent)
+        };
+        assert_eq!(digits("-0.0123"), ("123".to_owned(), -2));
+        assert_eq!(digits("1.500e3"), ("1500".to_owned(), 3));
+        assert_eq!(digits("000"), ("0".to_owned(), 0));
+        assert_eq!(Digits::parse("99.96").round(3).plain(), "100");
+        assert_eq!(Digits::parse("0.125").round(2).plain(), "0.13");
+        assert_eq!(Digits::parse("1.5").round(3).plain(), "1.50");
+    }
+
+    #[test]
+    fn test_notations() {
+        let format =
+            |notation: &str, value: f64| notation.parse::<Notation>().unwrap().format_f64(value);
+        assert_eq!(format("shortest", 0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format("fixed:2", 0.1 + 0.2), "0.30");
+        assert_eq!(format("significant:3", 0.1 + 0.2), "0.300");
+        assert_eq!(format("significant:3", 123456.0), "1.23e5");
+        assert_eq!(format("significant:3", -0.000123456), "-0.000123");
+        assert_eq!(format("scientific", 12345.0), "1.2345e4");
+        assert_eq!(format("scientific:2", 0.000987), "9.9e-4");
+        assert_eq!(format("engineering", 12345.0), "12.345e3");
+        assert_eq!(format("engineering:3", 0.0001), "100e-6");
+        assert_eq!(format("si:3", 4700.0), "4.70k");
+        assert_eq!(format("si", 0.000022), "22µ");
+        assert_eq!(format("si", 1e30), "1e30");
+        assert_eq!(format("engineering", std::f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn test_decimal() {
+        let notation = Notation::Significant(25);
+        assert_eq!(
+            notation.format_decimal("3.14159265358979323846264338"),
+            "3.141592653589793238462643"
+        );
+        assert_eq!(
+            Notation::Scientific(None).format_decimal("-120.50"),
+            "-1.2050e2"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        for name in &["shortest", "fixed:0", "significant:4", "scientific", "si:3"] {
+            assert_eq!(name.parse::<Notation>().unwrap().to_string(), *name);
+        }
+        assert!("significant".parse::<Notation>().is_err());
+        assert!("scientific:0".parse::<Notation>().is_err());
+        assert!("fixed:1000000000".parse::<Notation>().is_err());
+
+        // Exact results may have more digits than `f64` ones
+        let notation = "fixed:30".parse::<Notation>().unwrap();
+        assert_eq!(notation.check_digits(MAX_EXACT_DIGITS), Ok(notation));
+        assert!(notation.check_digits(MAX_DIGITS).is_err());
+        assert!(Notation::Scientific(None).check_digits(MAX_DIGITS).is_ok());
+    }
+}
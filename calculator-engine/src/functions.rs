@@ -0,0 +1,240 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Generates `Function` enum together with `extern "C"` runtime implementations.
+/// Runtime implementations are named after their libm counterparts and are shared between interpreter and JIT engines.
+macro_rules! builtin_functions {
+    ($($variant:ident($name:literal, $symbol:ident: $($argument:ident),+) => $body:expr,)+) => {
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum Function {
+            $($variant,)+
+        }
+
+        impl Function {
+            pub const ALL: &'static [Function] = &[$(Function::$variant,)+];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Function::$variant => $name,)+
+                }
+            }
+
+            /// Name of the symbol, under which function is registered in JIT module.
+            pub fn symbol(self) -> &'static str {
+                match self {
+                    $(Function::$variant => stringify!($symbol),)+
+                }
+            }
+
+            pub fn arity(self) -> usize {
+                match self {
+                    $(Function::$variant => [$(stringify!($argument)),+].len(),)+
+                }
+            }
+
+            pub fn address(self) -> *const u8 {
+                match self {
+                    $(Function::$variant => runtime::$symbol as *const u8,)+
+                }
+            }
+
+            pub fn call(self, arguments: &[f64]) -> f64 {
+                match (self, arguments) {
+                    $((Function::$variant, &[$($argument),+]) => runtime::$symbol($($argument),+),)+
+                    (function, arguments) => unreachable!(
+                        "Function {} called with {} arguments",
+                        function,
+                        arguments.len()
+                    ),
+                }
+            }
+        }
+
+        mod runtime {
+            use super::*;
+
+            $(
+                pub extern "C" fn $symbol($($argument: f64),+) -> f64 {
+                    $body
+                }
+            )+
+        }
+    };
+}
+
+builtin_functions! {
+    Sin("sin", sin: x) => x.sin(),
+    Cos("cos", cos: x) => x.cos(),
+    Tan("tan", tan: x) => x.tan(),
+    Asin("asin", asin: x) => x.asin(),
+    Acos("acos", acos: x) => x.acos(),
+    Atan("atan", atan: x) => x.atan(),
+    Atan2("atan2", atan2: y, x) => y.atan2(x),
+    Sinh("sinh", sinh: x) => x.sinh(),
+    Cosh("cosh", cosh: x) => x.cosh(),
+    Tanh("tanh", tanh: x) => x.tanh(),
+    Sqrt("sqrt", sqrt: x) => x.sqrt(),
+    Cbrt("cbrt", cbrt: x) => x.cbrt(),
+    Exp("exp", exp: x) => x.exp(),
+    Ln("ln", log: x) => x.ln(),
+    Log2("log2", log2: x) => x.log2(),
+    Log10("log10", log10: x) => x.log10(),
+    Pow("pow", pow: x, y) => x.powf(y),
+    Hypot("hypot", hypot: x, y) => x.hypot(y),
+    Abs("abs", fabs: x) => x.abs(),
+    Floor("floor", floor: x) => x.floor(),
+    Ceil("ceil", ceil: x) => x.ceil(),
+    Round("round", round: x) => x.round(),
+    Min("min", fmin: x, y) => x.min(y),
+    Max("max", fmax: x, y) => x.max(y),
+    Gamma("gamma", tgamma: x) => gamma(x),
+    Combinations("nCr", combinations: n, k) => binomial(n, k),
+    Permutations("nPr", permutations: n, k) => falling_factorial(n, k),
+}
+
+/// Lanczos approximation of gamma function, which is exact for positive integers.
+fn gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x == std::f64::INFINITY {
+        x
+    } else if x.fract() == 0.0 {
+        // Poles at non-positive integers, product overflows to infinity after 171
+        if x <= 0.0 {
+            std::f64::NAN
+        } else {
+            (2..x.min(172.0) as u64).map(|i| i as f64).product()
+        }
+    } else if x < 0.5 {
+        // Reflection formula
+        PI / ((PI * x).sin() * gamma(1.0 - x))
+    } else {
+        let x = x - 1.0;
+        let t = x + 7.5;
+        let sum = COEFFICIENTS[1..]
+            .iter()
+            .enumerate()
+            .fold(COEFFICIENTS[0], |sum, (i, coefficient)| {
+                sum + coefficient / (x + i as f64 + 1.0)
+            });
+
+        (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum
+    }
+}
+
+fn is_natural(x: f64) -> bool {
+    x >= 0.0 && x.fract() == 0.0
+}
+
+/// Binomial coefficient, every intermediate result is itself a binomial coefficient, so it's exact.
+fn binomial(n: f64, k: f64) -> f64 {
+    if !is_natural(n) || !is_natural(k) {
+        return std::f64::NAN;
+    }
+
+    let mut result = if k > n { 0.0 } else { 1.0 };
+    for i in 0..k.min(n - k).max(0.0) as u64 {
+        result = result * (n - i as f64) / (i as f64 + 1.0);
+        if result.is_infinite() {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Product of `k` factors `n * (n - 1) * ...`, i.e. number of ordered selections of `k` items out of `n`.
+fn falling_factorial(n: f64, k: f64) -> f64 {
+    if !is_natural(n) || !is_natural(k) {
+        return std::f64::NAN;
+    }
+
+    let mut result = if k > n { 0.0 } else { 1.0 };
+    for i in 0..k.min(n) as u64 {
+        result *= n - i as f64;
+        if result.is_infinite() {
+            break;
+        }
+    }
+
+    result
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        Function::ALL
+            .iter()
+            .copied()
+            .find(|function| function.name() == name)
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Function;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(Some(Function::Sqrt), Function::from_name("sqrt"));
+        assert_eq!(None, Function::from_name("unknown"));
+        assert_eq!(1, Function::Sin.arity());
+        assert_eq!(2, Function::Max.arity());
+        assert_eq!("fabs", Function::Abs.symbol());
+    }
+
+    #[test]
+    fn test_call() {
+        assert_eq!(2.0, Function::Sqrt.call(&[4.0]));
+        assert_eq!(3.0, Function::Max.call(&[1.0, 3.0]));
+        assert_eq!(8.0, Function::Pow.call(&[2.0, 3.0]));
+        assert_eq!(120.0, Function::Gamma.call(&[6.0]));
+        assert!((Function::Gamma.call(&[0.5]) - PI.sqrt()).abs() < 1e-14);
+        assert!((Function::Gamma.call(&[-0.5]) + 2.0 * PI.sqrt()).abs() < 1e-14);
+        assert!(Function::Gamma.call(&[-1.0]).is_nan());
+        assert_eq!(10.0, Function::Combinations.call(&[5.0, 2.0]));
+        assert_eq!(0.0, Function::Combinations.call(&[2.0, 5.0]));
+        assert_eq!(20.0, Function::Permutations.call(&[5.0, 2.0]));
+        assert!(Function::Permutations.call(&[5.5, 2.0]).is_nan());
+    }
+}
@@ -23,8 +23,16 @@
 
 #[allow(dead_code)]
 pub mod ast;
+pub mod constants;
+pub mod diagnostics;
 mod errors;
 pub mod execution;
+pub mod format;
+pub mod functions;
+pub mod number;
 pub mod parser;
+pub mod span;
+pub mod syntax;
+pub mod units;
 
 pub use errors::*;
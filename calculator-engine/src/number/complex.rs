@@ -0,0 +1,308 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::{Literal, Number, NumberError};
+use crate::constants;
+use crate::format::Notation;
+use crate::functions::Function;
+use num_complex::Complex64;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Complex number with `f64` parts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Complex(pub Complex64);
+
+impl Complex {
+    /// Drops sign of zero imaginary part, so real numbers always lie on the principal branch.
+    fn new(value: Complex64) -> Complex {
+        if value.im == 0.0 {
+            Complex(Complex64::new(value.re, 0.0))
+        } else {
+            Complex(value)
+        }
+    }
+
+    fn real(value: f64) -> Complex {
+        Complex::new(Complex64::new(value, 0.0))
+    }
+
+    /// Real value, when imaginary part is zero.
+    fn as_real(&self) -> Option<f64> {
+        if self.0.im == 0.0 {
+            Some(self.0.re)
+        } else {
+            None
+        }
+    }
+
+    /// Formats as `a + bi`, omitting zero parts.
+    fn format(&self, part: impl Fn(f64) -> String) -> String {
+        let Complex64 { re, im } = self.0;
+        let imaginary = |im: f64| {
+            if im == 1.0 {
+                "i".to_owned()
+            } else if im == -1.0 {
+                "-i".to_owned()
+            } else {
+                format!("{}i", part(im))
+            }
+        };
+
+        if im == 0.0 {
+            part(re)
+        } else if re == 0.0 {
+            imaginary(im)
+        } else if im < 0.0 {
+            format!("{} - {}", part(re), imaginary(-im))
+        } else {
+            format!("{} + {}", part(re), imaginary(im))
+        }
+    }
+
+    /// Formats with `digits` digits after the point in both parts.
+    /// Parts, which are zero at this precision, are omitted.
+    pub fn to_decimal(&self, digits: usize) -> String {
+        let round = |part: f64| {
+            if format!("{:.*}", digits, part.abs()).parse::<f64>() == Ok(0.0) {
+                0.0
+            } else {
+                part
+            }
+        };
+
+        Complex(Complex64::new(round(self.0.re), round(self.0.im)))
+            .format(|part| format!("{:.*}", digits, part))
+    }
+
+    fn undefined(operation: impl fmt::Display) -> NumberError {
+        NumberError::Undefined {
+            operation: operation.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Complex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.format(|part| part.to_string()))
+    }
+}
+
+impl Number for Complex {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        let parse = |text: &String| {
+            text.parse::<f64>()
+                .map_err(|_| NumberError::InvalidLiteral {
+                    literal: literal.to_string(),
+                })
+        };
+
+        match literal {
+            Literal::Decimal(text) => parse(text).map(Complex::real),
+            Literal::Constant(name) => Ok(Complex::real(constants::lookup(name).unwrap())),
+            Literal::Imaginary(text) => Ok(Complex::new(Complex64::new(0.0, parse(text)?))),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        Ok(Complex::real(value))
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.as_real().unwrap_or(std::f64::NAN)
+    }
+
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            Notation::Fixed(digits) => self.to_decimal(digits),
+            notation => self.format(|part| notation.format_f64(part)),
+        }
+    }
+
+    /// Only real numbers are ordered, other ones are either equal or not.
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(match (self.as_real(), other.as_real()) {
+            (Some(x), Some(y)) => x.partial_cmp(&y),
+            _ if self.0 == other.0 => Some(Ordering::Equal),
+            _ => None,
+        })
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(Complex::new(-self.0))
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Complex::new(self.0 + other.0))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Complex::new(self.0 - other.0))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Complex::new(self.0 * other.0))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        if other.0.re == 0.0 && other.0.im == 0.0 {
+            Err(NumberError::DivisionByZero)
+        } else {
+            Ok(Complex::new(self.0 / other.0))
+        }
+    }
+
+    /// Integer exponents are computed by multiplication, so `i ^ 2` is exactly `-1`.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        match (self.as_real(), other.as_real()) {
+            (Some(base), Some(exponent)) if base >= 0.0 => Ok(Complex::real(base.powf(exponent))),
+            (_, Some(exponent))
+                if exponent.fract() == 0.0 && exponent.abs() <= f64::from(std::i32::MAX) =>
+            {
+                if self.0.re == 0.0 && self.0.im == 0.0 && exponent < 0.0 {
+                    Err(NumberError::DivisionByZero)
+                } else {
+                    Ok(Complex::new(self.0.powi(exponent as i32)))
+                }
+            }
+            _ => Ok(Complex::new(self.0.powc(other.0))),
+        }
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        // Functions of real arguments, which have no complex extension
+        let real = || {
+            let arguments = arguments
+                .iter()
+                .map(Complex::as_real)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    Complex::undefined(format!("function {} of complex numbers", function))
+                })?;
+            Ok(Complex::real(function.call(&arguments)))
+        };
+
+        let z = arguments[0].0;
+        let value = match function {
+            Function::Sin => z.sin(),
+            Function::Cos => z.cos(),
+            Function::Tan => z.tan(),
+            Function::Asin => z.asin(),
+            Function::Acos => z.acos(),
+            Function::Atan => z.atan(),
+            Function::Sinh => z.sinh(),
+            Function::Cosh => z.cosh(),
+            Function::Tanh => z.tanh(),
+            Function::Sqrt => z.sqrt(),
+            // Real cube root of negative number is preferred to the principal one
+            Function::Cbrt if z.im == 0.0 => return real(),
+            Function::Cbrt => z.cbrt(),
+            Function::Exp => z.exp(),
+            Function::Ln => z.ln(),
+            Function::Log2 => z.log(2.0),
+            Function::Log10 => z.log(10.0),
+            Function::Pow => return arguments[0].pow(&arguments[1]),
+            Function::Abs => Complex64::new(z.norm(), 0.0),
+            Function::Floor => Complex64::new(z.re.floor(), z.im.floor()),
+            Function::Ceil => Complex64::new(z.re.ceil(), z.im.ceil()),
+            Function::Round => Complex64::new(z.re.round(), z.im.round()),
+            Function::Atan2
+            | Function::Hypot
+            | Function::Min
+            | Function::Max
+            | Function::Gamma
+            | Function::Combinations
+            | Function::Permutations => return real(),
+        };
+
+        Ok(Complex::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::Interpreter;
+    use crate::syntax::{Locale, Syntax};
+    use crate::Error;
+
+    fn exec(s: &str) -> Result<String, Error> {
+        let complex = Syntax {
+            imaginary: true,
+            ..Syntax::default()
+        };
+        complex
+            .scope(|| Interpreter::exec(s, &mut Environment::<Complex>::default()))
+            .map(|result| result.unwrap().to_string())
+    }
+
+    #[test]
+    fn test_complex() {
+        assert_eq!(exec("sqrt(-1)").unwrap(), "i");
+        assert_eq!(exec("i ^ 2").unwrap(), "-1");
+        assert_eq!(exec("(1 + 2i) * (3 - i)").unwrap(), "5 + 5i");
+        assert_eq!(exec("(1 + 2i) / 2").unwrap(), "0.5 + i");
+        assert_eq!(exec("2 - 3i").unwrap(), "2 - 3i");
+        assert_eq!(exec("-i").unwrap(), "-i");
+        assert_eq!(exec("abs(3 + 4i)").unwrap(), "5");
+        assert_eq!(exec("cbrt(-8)").unwrap(), "-2");
+        assert_eq!(
+            exec("ln(-1)").unwrap(),
+            format!("{}i", std::f64::consts::PI)
+        );
+        assert_eq!(
+            Complex::new(Complex64::new(1.0, -1.0 / 3.0)).to_decimal(2),
+            "1.00 - 0.33i"
+        );
+        assert_eq!(
+            Complex::new(Complex64::new(-1.0, 1.2e-16)).to_decimal(3),
+            "-1.000"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(exec("1 / (i - i)").is_err());
+        assert!(exec("max(i, 1)").is_err());
+        assert!(exec("i = 3").is_err());
+        assert_eq!(exec("max(2, 1)").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_locale() {
+        // Like in the GTK app, where the keypad has `i` and numbers are localized
+        let syntax = Syntax {
+            imaginary: true,
+            locale: Locale::GERMAN,
+            ..Syntax::default()
+        };
+        let result = syntax
+            .scope(|| Interpreter::exec("(1,5 + i) * i", &mut Environment::<Complex>::default()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(Locale::GERMAN.format(&result.to_string()), "-1 + 1,5i");
+    }
+}
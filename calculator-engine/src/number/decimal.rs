@@ -0,0 +1,591 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::rational::MAX_EXPONENT;
+use super::{exact_combinatorics, Literal, Number, NumberError};
+use crate::format::Notation;
+use crate::functions::Function;
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{pow, One, Signed, ToPrimitive, Zero};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// Digits, which are computed beyond precision in intermediate results.
+const GUARD_DIGITS: u64 = 10;
+
+/// Largest precision, powers of ten with more digits take too long to compute.
+pub const MAX_PRECISION: u64 = 10_000;
+
+/// How result is rounded to the precision.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// To the nearest, ties go to the even digit.
+    HalfEven,
+    /// To the nearest, ties go away from zero.
+    HalfUp,
+    /// Extra digits are dropped.
+    TowardZero,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(Rounding::HalfEven),
+            "half-up" => Ok(Rounding::HalfUp),
+            "toward-zero" => Ok(Rounding::TowardZero),
+            _ => Err(format!(
+                "Unknown rounding {}, expected half-even, half-up or toward-zero",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Rounding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Rounding::HalfEven => write!(f, "half-even"),
+            Rounding::HalfUp => write!(f, "half-up"),
+            Rounding::TowardZero => write!(f, "toward-zero"),
+        }
+    }
+}
+
+/// Precision and rounding of decimal arithmetic.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Context {
+    /// Significant digits of every result.
+    pub precision: u64,
+    pub rounding: Rounding,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            precision: 34,
+            rounding: Rounding::HalfEven,
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXT: Cell<Context> = Cell::new(Context::default());
+}
+
+impl Context {
+    /// Context of the running evaluation.
+    pub fn current() -> Context {
+        CONTEXT.with(Cell::get)
+    }
+
+    /// Evaluates `f` with this context, restoring the previous one afterwards.
+    pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Context);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CONTEXT.with(|context| context.set(self.0));
+            }
+        }
+
+        let _restore = Restore(CONTEXT.with(|context| context.replace(self)));
+        f()
+    }
+
+    /// Same context with guard digits, which is used for intermediate results.
+    fn working(self) -> Context {
+        Context {
+            precision: self.precision + GUARD_DIGITS,
+            rounding: Rounding::HalfEven,
+        }
+    }
+}
+
+fn ten_to_the(exponent: u64) -> BigInt {
+    pow(BigInt::from(10), exponent as usize)
+}
+
+fn count_digits(value: &BigInt) -> u64 {
+    value.abs().to_string().len() as u64
+}
+
+/// Drops `excess` last digits, rounding the rest.
+fn round_digits(digits: BigInt, excess: u64, rounding: Rounding) -> BigInt {
+    if excess == 0 {
+        return digits;
+    }
+
+    let divisor = ten_to_the(excess);
+    let (quotient, remainder) = digits.div_rem(&divisor);
+    let twice = remainder.abs() * 2;
+    let away = match rounding {
+        Rounding::HalfEven => twice > divisor || (twice == divisor && quotient.is_odd()),
+        Rounding::HalfUp => twice >= divisor,
+        Rounding::TowardZero => false,
+    };
+
+    if away {
+        quotient + digits.signum()
+    } else {
+        quotient
+    }
+}
+
+/// Arbitrary-precision decimal floating point number.
+///
+/// Literals keep every typed digit, results of operations are rounded according to `Context::current`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Decimal(pub BigDecimal);
+
+impl Decimal {
+    /// Rounds `digits * 10^-scale` to the precision of `context`.
+    /// `inexact` tells that the exact value has non-zero digits beyond `digits`.
+    /// Results with decimal exponent beyond `MAX_EXPONENT` overflow.
+    fn round(
+        digits: BigInt,
+        scale: i64,
+        inexact: bool,
+        context: Context,
+    ) -> Result<Decimal, NumberError> {
+        // Non-zero digit after the last one makes ties in the remainder distinguishable from the exact ones
+        let (digits, scale) = if inexact {
+            (digits.clone() * 10 + digits.signum(), scale + 1)
+        } else {
+            (digits, scale)
+        };
+
+        let excess = count_digits(&digits).saturating_sub(context.precision);
+        let mut digits = round_digits(digits, excess, context.rounding);
+        let mut scale = scale - excess as i64;
+
+        if digits.is_zero() {
+            return Ok(Decimal(BigDecimal::zero()));
+        }
+
+        let exponent = count_digits(&digits) as i64 - 1 - scale;
+        if exponent.abs() > MAX_EXPONENT {
+            return Err(NumberError::Overflow {
+                operation: format!("result with exponent {}", exponent),
+            });
+        }
+
+        let ten = BigInt::from(10);
+        while (&digits % &ten).is_zero() {
+            digits /= &ten;
+            scale -= 1;
+        }
+
+        Ok(Decimal(BigDecimal::new(digits, scale)))
+    }
+
+    fn rounded(value: BigDecimal) -> Result<Decimal, NumberError> {
+        let (digits, scale) = value.into_bigint_and_exponent();
+        Decimal::round(digits, scale, false, Context::current())
+    }
+
+    /// Decimal expansion with `digits` digits after the point.
+    pub fn to_decimal(&self, digits: usize, rounding: Rounding) -> String {
+        let (value, scale) = self.0.as_bigint_and_exponent();
+        let rounded = if scale > digits as i64 {
+            let excess = (scale - digits as i64) as u64;
+            BigDecimal::new(round_digits(value, excess, rounding), digits as i64)
+        } else {
+            self.0.clone()
+        };
+
+        format!("{:.*}", digits, rounded)
+    }
+
+    /// Root of the given degree, rounded to the precision.
+    fn root(&self, degree: u32) -> Result<Decimal, NumberError> {
+        if self.0.is_negative() {
+            return if degree % 2 == 0 {
+                Err(Decimal::undefined(format!(
+                    "even root of negative number {}",
+                    self
+                )))
+            } else {
+                Decimal(self.0.abs()).root(degree)?.neg()
+            };
+        }
+
+        let (digits, scale) = self.0.as_bigint_and_exponent();
+        if digits.is_zero() {
+            return Ok(self.clone());
+        }
+
+        let context = Context::current();
+        let degree = u64::from(degree);
+
+        // Radicand gets enough digits for the precision and scale divisible by the degree
+        let mut shift = (degree * (context.precision + 1)).saturating_sub(count_digits(&digits));
+        shift += (degree - (scale + shift as i64).mod_floor(&(degree as i64)) as u64) % degree;
+
+        let radicand = digits * ten_to_the(shift);
+        let root = radicand.nth_root(degree as u32);
+        let inexact = pow(root.clone(), degree as usize) != radicand;
+
+        Decimal::round(
+            root,
+            (scale + shift as i64) / degree as i64,
+            inexact,
+            context,
+        )
+    }
+
+    /// Constant computed to the precision.
+    fn constant(name: &str) -> Option<Decimal> {
+        let context = Context::current();
+        let precision = context.working().precision;
+        let unit = ten_to_the(precision);
+
+        // Arctangent of 1/x in fixed point with `precision` digits
+        let arctan = |x: u32| {
+            let x = BigInt::from(x);
+            let square = &x * &x;
+            let mut power = &unit / &x;
+            let mut sum = BigInt::zero();
+            let mut k = 0u32;
+            while !power.is_zero() {
+                let term = &power / BigInt::from(2 * k + 1);
+                if k % 2 == 0 {
+                    sum += term;
+                } else {
+                    sum -= term;
+                }
+                power /= &square;
+                k += 1;
+            }
+            sum
+        };
+
+        let value = match name {
+            // Machin's formula
+            "pi" => (arctan(5) * 4 - arctan(239)) * 4,
+            "tau" => (arctan(5) * 4 - arctan(239)) * 8,
+            "e" => {
+                let mut term = unit.clone();
+                let mut sum = BigInt::zero();
+                let mut k = 1u32;
+                while !term.is_zero() {
+                    sum += &term;
+                    term /= BigInt::from(k);
+                    k += 1;
+                }
+                sum
+            }
+            _ => return None,
+        };
+
+        Decimal::round(value, precision as i64, true, context).ok()
+    }
+
+    fn undefined(operation: impl fmt::Display) -> NumberError {
+        NumberError::Undefined {
+            operation: operation.to_string(),
+        }
+    }
+
+    fn unsupported(operation: impl fmt::Display) -> NumberError {
+        NumberError::Unsupported {
+            operation: operation.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Number for Decimal {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Decimal(text) => {
+                let value =
+                    BigDecimal::from_str(text).map_err(|_| NumberError::InvalidLiteral {
+                        literal: text.clone(),
+                    })?;
+
+                // Huge exponents are rejected before any digits are written out
+                let (digits, scale) = value.as_bigint_and_exponent();
+                if !digits.is_zero() && scale.abs() > MAX_EXPONENT {
+                    Err(NumberError::Overflow {
+                        operation: format!("literal {}", text),
+                    })
+                } else {
+                    Ok(Decimal(value))
+                }
+            }
+            Literal::Constant(name) => Decimal::constant(name)
+                .ok_or_else(|| Decimal::undefined(format!("constant {}", name))),
+            Literal::Imaginary(_) => Err(Decimal::unsupported(format!(
+                "imaginary number {}",
+                literal
+            ))),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        if value.is_finite() {
+            Ok(Decimal(BigDecimal::from_str(&value.to_string()).unwrap()))
+        } else {
+            Err(Decimal::undefined(value))
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.0.to_string().parse().unwrap_or(std::f64::NAN)
+    }
+
+    /// Keeps all digits of the precision, fixed notation is rounded like the context.
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            Notation::Fixed(digits) => self.to_decimal(digits, Context::current().rounding),
+            notation => notation.format_decimal(&self.to_string()),
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(Some(self.0.cmp(&other.0)))
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(Decimal(-&self.0))
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        Decimal::rounded(&self.0 + &other.0)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        Decimal::rounded(&self.0 - &other.0)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        Decimal::rounded(&self.0 * &other.0)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        if other.0.is_zero() {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        let context = Context::current();
+        let (numerator, numerator_scale) = self.0.as_bigint_and_exponent();
+        let (denominator, denominator_scale) = other.0.as_bigint_and_exponent();
+        if numerator.is_zero() {
+            return Ok(self.clone());
+        }
+
+        // Quotient gets at least one digit more than the precision
+        let shift = (context.precision + 1 + count_digits(&denominator))
+            .saturating_sub(count_digits(&numerator));
+        let (quotient, remainder) = (numerator * ten_to_the(shift)).div_rem(&denominator);
+
+        Decimal::round(
+            quotient,
+            numerator_scale - denominator_scale + shift as i64,
+            !remainder.is_zero(),
+            context,
+        )
+    }
+
+    /// Power is supported for integer exponents only.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        let exponent = match other.0.to_i64() {
+            Some(exponent) if other.0.is_integer() => exponent,
+            _ => return Err(Decimal::unsupported(format!("{} ^ {}", self, other))),
+        };
+
+        if self.0.is_zero() && exponent < 0 {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        let context = Context::current();
+        let power = context
+            .working()
+            .scope(|| -> Result<Decimal, NumberError> {
+                let mut power = Decimal(BigDecimal::one());
+                let mut base = self.clone();
+                let mut remaining = exponent.abs();
+                while remaining > 0 {
+                    if remaining % 2 == 1 {
+                        power = power.mul(&base)?;
+                    }
+                    base = base.mul(&base)?;
+                    remaining /= 2;
+                }
+
+                if exponent < 0 {
+                    Decimal(BigDecimal::one()).div(&power)
+                } else {
+                    Ok(power)
+                }
+            })?;
+
+        Decimal::rounded(power.0)
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        let integer = |x: &Decimal, function| {
+            let (digits, scale) = x.0.as_bigint_and_exponent();
+            if scale <= 0 {
+                return x.clone();
+            }
+            let (quotient, remainder) = digits.div_mod_floor(&ten_to_the(scale as u64));
+            let twice = remainder.clone() * 2;
+            let divisor = ten_to_the(scale as u64);
+            let up = match function {
+                Function::Floor => false,
+                Function::Ceil => !remainder.is_zero(),
+                // Half away from zero, like `f64::round`
+                _ => twice > divisor || (twice == divisor && !digits.is_negative()),
+            };
+            Decimal(BigDecimal::new(if up { quotient + 1 } else { quotient }, 0))
+        };
+
+        if let Some(result) = exact_combinatorics(function, arguments) {
+            return result;
+        }
+
+        match (function, arguments) {
+            (Function::Abs, [x]) => Ok(Decimal(x.0.abs())),
+            (Function::Floor, [x]) | (Function::Ceil, [x]) | (Function::Round, [x]) => {
+                Ok(integer(x, function))
+            }
+            (Function::Min, [x, y]) => Ok(if y.0 < x.0 { y.clone() } else { x.clone() }),
+            (Function::Max, [x, y]) => Ok(if y.0 > x.0 { y.clone() } else { x.clone() }),
+            (Function::Pow, [x, y]) => x.pow(y),
+            (Function::Sqrt, [x]) => x.root(2),
+            (Function::Cbrt, [x]) => x.root(3),
+            (Function::Hypot, [x, y]) => {
+                let context = Context::current();
+                let square = context.working().scope(|| x.mul(x)?.add(&y.mul(y)?))?;
+                square.root(2)
+            }
+            _ => Err(Decimal::unsupported(format!("function {}", function))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::{Interpreter, InterpreterError};
+    use crate::Error;
+
+    fn exec(s: &str, precision: u64, rounding: Rounding) -> Result<String, Error> {
+        Context {
+            precision,
+            rounding,
+        }
+        .scope(|| Interpreter::exec(s, &mut Environment::<Decimal>::default()))
+        .map(|result| result.unwrap().to_string())
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(
+            exec("sqrt(2)", 100, Rounding::HalfEven).unwrap(),
+            "1.414213562373095048801688724209698078569671875376948073176679737990732478462107038850387534327641573"
+        );
+        assert_eq!(
+            exec("2 * pi / 2", 50, Rounding::HalfEven).unwrap(),
+            "3.1415926535897932384626433832795028841971693993751"
+        );
+        assert_eq!(
+            exec(
+                "0.1000000000000000000000000001 + 0.2",
+                40,
+                Rounding::HalfEven
+            )
+            .unwrap(),
+            "0.3000000000000000000000000001"
+        );
+        assert_eq!(exec("2 ^ -3 * 1e3", 5, Rounding::HalfEven).unwrap(), "125");
+        assert_eq!(exec("cbrt(-27)", 5, Rounding::HalfEven).unwrap(), "-3");
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(
+            exec("2/3", 30, Rounding::HalfEven).unwrap(),
+            "0.666666666666666666666666666667"
+        );
+        assert_eq!(
+            exec("2/3", 30, Rounding::TowardZero).unwrap(),
+            "0.666666666666666666666666666666"
+        );
+        assert_eq!(exec("2.345 * 1", 3, Rounding::HalfEven).unwrap(), "2.34");
+        assert_eq!(exec("2.355 * 1", 3, Rounding::HalfEven).unwrap(), "2.36");
+        assert_eq!(exec("2.345 * 1", 3, Rounding::HalfUp).unwrap(), "2.35");
+        assert_eq!(exec("-2.5", 3, Rounding::HalfEven).unwrap(), "-2.5");
+
+        let third = Context::default().scope(|| {
+            Decimal::from_literal(&Literal::Decimal("2".to_owned()))?
+                .div(&Decimal::from_literal(&Literal::Decimal("3".to_owned()))?)
+        });
+        assert_eq!(third.unwrap().to_decimal(3, Rounding::TowardZero), "0.666");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(exec("1 / 0", 10, Rounding::HalfEven).is_err());
+        assert!(exec("sqrt(-1)", 10, Rounding::HalfEven).is_err());
+        assert!(exec("2 ^ 0.5", 10, Rounding::HalfEven).is_err());
+        assert!(exec("inf", 10, Rounding::HalfEven).is_err());
+        assert!(exec("(5 + 10^-20)!", 34, Rounding::HalfEven).is_err());
+        assert!(exec("nCr(5 + 10^-20, 2)", 34, Rounding::HalfEven).is_err());
+
+        // Huge exponents are rejected instead of writing out their zeros
+        for source in &[
+            "1e999999999 + 1",
+            "1e-999999999",
+            "1e9000 * 1e9000",
+            "2 ^ 1000000000",
+        ] {
+            match exec(source, 10, Rounding::HalfEven).unwrap_err() {
+                Error::InterpreterError(InterpreterError::Arithmetic {
+                    source: NumberError::Overflow { .. },
+                    ..
+                }) => {}
+                e => panic!("Unexpected error {:?}", e),
+            }
+        }
+        assert_eq!(
+            exec("0e999999999 + 1e3", 10, Rounding::HalfEven).unwrap(),
+            "1000"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::{Literal, Number, NumberError};
+use crate::functions::Function;
+
+impl Number for f64 {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Imaginary(_) => Err(NumberError::Unsupported {
+                operation: format!("imaginary number {}", literal),
+            }),
+            literal => Ok(literal.to_f64()),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        Ok(value)
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(-self)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(self + other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(self - other)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(self * other)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(self / other)
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(self.powf(*other))
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        Ok(function.call(arguments))
+    }
+}
@@ -0,0 +1,374 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::rational::parse_decimal;
+use super::{exact_combinatorics, Literal, Number, NumberError};
+use crate::format::Notation;
+use crate::functions::Function;
+use num_integer::Roots;
+use num_traits::{checked_pow, ToPrimitive};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Machine integer, which reports overflow instead of wrapping.
+///
+/// It's transparent, so JIT compiled code reads variable values as `i64`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Integer(pub i64);
+
+impl Integer {
+    fn overflow(operation: impl fmt::Display) -> NumberError {
+        NumberError::Overflow {
+            operation: operation.to_string(),
+        }
+    }
+
+    fn not_integer(value: impl fmt::Display) -> NumberError {
+        NumberError::NotInteger {
+            value: value.to_string(),
+        }
+    }
+
+    fn check_divisor(&self) -> Result<(), NumberError> {
+        if self.0 == 0 {
+            Err(NumberError::DivisionByZero)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Digits in the given radix with prefix like `0x`, e.g. `-0x1F`.
+    pub fn to_radix(self, radix: u32) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        // Wrapped absolute value of `i64::MIN` is still right, when it's reinterpreted as unsigned
+        let magnitude = self.0.wrapping_abs() as u64;
+
+        match radix {
+            2 => format!("{}0b{:b}", sign, magnitude),
+            8 => format!("{}0o{:o}", sign, magnitude),
+            16 => format!("{}0x{:X}", sign, magnitude),
+            _ => self.to_string(),
+        }
+    }
+
+    fn check_shift(&self, other: &Self) -> Result<(), NumberError> {
+        if other.0 < 0 {
+            Err(NumberError::Undefined {
+                operation: format!("shift of {} by negative amount {}", self, other),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Root of the given degree, when it's an integer.
+    fn root(self, degree: u32) -> Option<Integer> {
+        if degree % 2 == 0 && self.0 < 0 {
+            return None;
+        }
+
+        let root = self.0.nth_root(degree);
+        if checked_pow(root, degree as usize) == Some(self.0) {
+            Some(Integer(root))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Integer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Number for Integer {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Decimal(text) => {
+                // Larger powers of ten don't fit into `i64` anyway
+                let value = parse_decimal(text, 18)?;
+
+                if !value.is_integer() {
+                    return Err(Integer::not_integer(text));
+                }
+
+                value
+                    .to_integer()
+                    .to_i64()
+                    .map(Integer)
+                    .ok_or_else(|| Integer::overflow(format!("literal {}", text)))
+            }
+            Literal::Constant(name) => Err(Integer::not_integer(format!("constant {}", name))),
+            Literal::Imaginary(_) => Err(NumberError::Unsupported {
+                operation: format!("imaginary number {}", literal),
+            }),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        // Bounds are powers of two, so they are exact
+        if value.fract() == 0.0
+            && (-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&value)
+        {
+            Ok(Integer(value as i64))
+        } else {
+            Err(Integer::not_integer(value))
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.0 as f64
+    }
+
+    fn to_notation(&self, notation: Notation) -> String {
+        notation.format_decimal(&self.to_string())
+    }
+
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(Some(self.0.cmp(&other.0)))
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        self.0
+            .checked_neg()
+            .map(Integer)
+            .ok_or_else(|| Integer::overflow(format!("-{}", self)))
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        self.0
+            .checked_add(other.0)
+            .map(Integer)
+            .ok_or_else(|| Integer::overflow(format!("{} + {}", self, other)))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Integer)
+            .ok_or_else(|| Integer::overflow(format!("{} - {}", self, other)))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        self.0
+            .checked_mul(other.0)
+            .map(Integer)
+            .ok_or_else(|| Integer::overflow(format!("{} * {}", self, other)))
+    }
+
+    /// Division truncates toward zero.
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        other.check_divisor()?;
+        self.0
+            .checked_div(other.0)
+            .map(Integer)
+            .ok_or_else(|| Integer::overflow(format!("{} / {}", self, other)))
+    }
+
+    /// Power is an integer only for non-negative exponents, unless the base is `1` or `-1`.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        let overflow = || Integer::overflow(format!("{} ^ {}", self, other));
+
+        match (self.0, other.0) {
+            (0, exponent) if exponent < 0 => Err(NumberError::DivisionByZero),
+            (1, _) => Ok(Integer(1)),
+            (-1, exponent) => Ok(Integer(if exponent % 2 == 0 { 1 } else { -1 })),
+            (_, exponent) if exponent < 0 => {
+                Err(Integer::not_integer(format!("{} ^ {}", self, other)))
+            }
+            (base, exponent) => checked_pow(base, exponent.to_usize().ok_or_else(overflow)?)
+                .map(Integer)
+                .ok_or_else(overflow),
+        }
+    }
+
+    fn floor_div(&self, other: &Self) -> Result<Self, NumberError> {
+        let quotient = self.div(other)?.0;
+
+        if self.rem(other)?.0 == 0 {
+            Ok(Integer(quotient))
+        } else {
+            // Truncated quotient is above the exact one, when signs of operands differ
+            Ok(Integer(quotient - i64::from((self.0 < 0) != (other.0 < 0))))
+        }
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, NumberError> {
+        other.check_divisor()?;
+
+        // Only `MIN % -1` overflows, and its remainder is zero
+        let remainder = self.0.checked_rem(other.0).unwrap_or(0);
+        if remainder != 0 && (remainder < 0) != (other.0 < 0) {
+            Ok(Integer(remainder + other.0))
+        } else {
+            Ok(Integer(remainder))
+        }
+    }
+
+    fn bit_and(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Integer(self.0 & other.0))
+    }
+
+    fn bit_or(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Integer(self.0 | other.0))
+    }
+
+    fn bit_xor(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Integer(self.0 ^ other.0))
+    }
+
+    fn bit_not(&self) -> Result<Self, NumberError> {
+        Ok(Integer(!self.0))
+    }
+
+    /// Left shift overflows, when any significant bit is shifted out.
+    fn shl(&self, other: &Self) -> Result<Self, NumberError> {
+        self.check_shift(other)?;
+
+        match other.0 {
+            shift if shift < 64 && (self.0 << shift) >> shift == self.0 => {
+                Ok(Integer(self.0 << shift))
+            }
+            _ if self.0 == 0 => Ok(*self),
+            _ => Err(Integer::overflow(format!("{} << {}", self, other))),
+        }
+    }
+
+    /// Arithmetic right shift, which keeps the sign.
+    fn shr(&self, other: &Self) -> Result<Self, NumberError> {
+        self.check_shift(other)?;
+        Ok(Integer(self.0 >> other.0.min(63)))
+    }
+
+    /// Functions without integer counterpart are evaluated over `f64` and must produce an integer.
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        if let Some(result) = exact_combinatorics(function, arguments) {
+            return result;
+        }
+
+        match (function, arguments) {
+            (Function::Abs, [x]) => {
+                x.0.checked_abs()
+                    .map(Integer)
+                    .ok_or_else(|| Integer::overflow(format!("function {}", function)))
+            }
+            (Function::Floor, [x]) | (Function::Ceil, [x]) | (Function::Round, [x]) => Ok(*x),
+            (Function::Min, [x, y]) => Ok(*x.min(y)),
+            (Function::Max, [x, y]) => Ok(*x.max(y)),
+            (Function::Pow, [x, y]) => x.pow(y),
+            (Function::Sqrt, [x]) if x.0 < 0 => Err(NumberError::Undefined {
+                operation: format!("function {} of negative number", function),
+            }),
+            (Function::Sqrt, [x]) => x
+                .root(2)
+                .ok_or_else(|| Integer::not_integer(format!("function {}", function))),
+            (Function::Cbrt, [x]) => x
+                .root(3)
+                .ok_or_else(|| Integer::not_integer(format!("function {}", function))),
+            _ => Integer::from_f64(
+                function.call(&arguments.iter().map(Number::to_f64).collect::<Vec<_>>()),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::{Interpreter, InterpreterError};
+    use crate::Error;
+
+    fn exec(s: &str) -> Result<Integer, Error> {
+        Interpreter::exec(s, &mut Environment::<Integer>::default()).map(Option::unwrap)
+    }
+
+    #[test]
+    fn test_division() {
+        assert_eq!(exec("7 / 2").unwrap(), Integer(3));
+        assert_eq!(exec("-7 / 2").unwrap(), Integer(-3));
+        assert_eq!(exec("-7 // 2").unwrap(), Integer(-4));
+        assert_eq!(exec("7 // -2").unwrap(), Integer(-4));
+        assert_eq!(exec("-7 % 2").unwrap(), Integer(1));
+        assert_eq!(exec("7 % -2").unwrap(), Integer(-1));
+        assert_eq!(
+            exec("2 ^ 62 + sqrt(49) - abs(-1)").unwrap(),
+            Integer((1 << 62) + 6)
+        );
+    }
+
+    #[test]
+    fn test_bitwise() {
+        assert_eq!(exec("0xF0 | 0x0F & 0b0110").unwrap(), Integer(0xF6));
+        assert_eq!(exec("1 << 4 + 1").unwrap(), Integer(32));
+        assert_eq!(exec("6 xor 3").unwrap(), Integer(5));
+        assert_eq!(exec("~0 >> 100").unwrap(), Integer(-1));
+        assert!(exec("1 << 63").is_err());
+        assert!(exec("1 >> -1").is_err());
+
+        assert_eq!(Integer(31).to_radix(16), "0x1F");
+        assert_eq!(Integer(-5).to_radix(2), "-0b101");
+        assert_eq!(
+            Integer(std::i64::MIN).to_radix(8),
+            "-0o1000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        match exec("1 + 9223372036854775807 * 2").unwrap_err() {
+            Error::InterpreterError(InterpreterError::Arithmetic {
+                source: NumberError::Overflow { operation },
+                span,
+            }) => {
+                assert_eq!(operation, "9223372036854775807 * 2");
+                assert_eq!((span.start, span.end), (4, 27));
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        assert!(exec("-9223372036854775807 - 2").is_err());
+        assert!(exec("(-9223372036854775807 - 1) / -1").is_err());
+        assert!(exec("1 % 0").is_err());
+        assert!(exec("1.5").is_err());
+        assert!(exec("2 ^ 64").is_err());
+        assert!(exec("sqrt(2)").is_err());
+        assert!(exec("21!").is_err());
+        assert_eq!(exec("20! / nPr(20, 19)").unwrap(), Integer(1));
+        assert_eq!(exec("1.5e3").unwrap(), Integer(1500));
+        assert_eq!(exec("(-9223372036854775807 - 1) % -1").unwrap(), Integer(0));
+
+        match exec("1e999999999999").unwrap_err() {
+            Error::InterpreterError(InterpreterError::Arithmetic {
+                source: NumberError::Overflow { operation },
+                ..
+            }) => assert_eq!(operation, "literal 1e999999999999"),
+            e => panic!("Unexpected error {:?}", e),
+        }
+        assert!(exec("1e19").is_err());
+        assert_eq!(exec("9.2e18").unwrap(), Integer(9_200_000_000_000_000_000));
+    }
+}
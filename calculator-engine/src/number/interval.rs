@@ -0,0 +1,560 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::rational::{parse_decimal, Rational, MAX_EXPONENT};
+use super::{Literal, Number, NumberError};
+use crate::constants;
+use crate::format::Notation;
+use crate::functions::Function;
+use num_rational::BigRational;
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Smallest `f64` greater than `x`.
+fn next_up(x: f64) -> f64 {
+    if x.is_nan() || x == std::f64::INFINITY {
+        x
+    } else if x == 0.0 {
+        f64::from_bits(1)
+    } else if x > 0.0 {
+        f64::from_bits(x.to_bits() + 1)
+    } else {
+        f64::from_bits(x.to_bits() - 1)
+    }
+}
+
+/// Largest `f64` less than `x`.
+fn next_down(x: f64) -> f64 {
+    -next_up(-x)
+}
+
+/// Magnitude, below which error terms of multiplication and division aren't exact.
+const TINY: f64 = 1e-290;
+
+/// Bounds of the exact value, which was rounded to nearest `value`.
+/// Sign of `error` tells on which side of `value` the exact one is.
+fn directed(value: f64, error: f64) -> (f64, f64) {
+    // Overflow
+    if value.is_infinite() {
+        (next_down(value), next_up(value))
+    } else if error > 0.0 {
+        (value, next_up(value))
+    } else if error < 0.0 {
+        (next_down(value), value)
+    } else {
+        (value, value)
+    }
+}
+
+fn add(a: f64, b: f64) -> (f64, f64) {
+    // Error-free transformation of the sum
+    let sum = a + b;
+    let b_virtual = sum - a;
+    directed(sum, (a - (sum - b_virtual)) + (b - b_virtual))
+}
+
+fn mul(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    if a == 0.0 || b == 0.0 {
+        (product, product)
+    } else if product.abs() < TINY {
+        (next_down(product), next_up(product))
+    } else {
+        directed(product, a.mul_add(b, -product))
+    }
+}
+
+fn div(a: f64, b: f64) -> (f64, f64) {
+    let quotient = a / b;
+    if a == 0.0 {
+        (quotient, quotient)
+    } else if quotient.abs() < TINY || a.abs() < TINY {
+        (next_down(quotient), next_up(quotient))
+    } else {
+        let remainder = (-quotient).mul_add(b, a);
+        directed(quotient, if b > 0.0 { remainder } else { -remainder })
+    }
+}
+
+fn sqrt(a: f64) -> (f64, f64) {
+    let root = a.sqrt();
+    if a == 0.0 {
+        (root, root)
+    } else if a < TINY {
+        (next_down(root), next_up(root))
+    } else {
+        directed(root, (-root).mul_add(root, a))
+    }
+}
+
+/// Closed interval, which is guaranteed to contain the exact result.
+///
+/// Bounds of every operation are rounded outwards, library functions are assumed to be accurate to two ulps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Interval {
+    pub fn new(lower: f64, upper: f64) -> Interval {
+        Interval { lower, upper }
+    }
+
+    fn point(value: f64) -> Interval {
+        Interval::new(value, value)
+    }
+
+    /// Encloses results of library function at the bounds.
+    fn approximate(lower: f64, upper: f64) -> Interval {
+        Interval::new(next_down(next_down(lower)), next_up(next_up(upper)))
+    }
+
+    fn min(&self, other: &Interval) -> Interval {
+        Interval::new(self.lower.min(other.lower), self.upper.min(other.upper))
+    }
+
+    fn max(&self, other: &Interval) -> Interval {
+        Interval::new(self.lower.max(other.lower), self.upper.max(other.upper))
+    }
+
+    fn contains_zero(&self) -> bool {
+        self.lower <= 0.0 && self.upper >= 0.0
+    }
+
+    /// Interval of absolute values.
+    fn magnitude(&self) -> Interval {
+        if self.contains_zero() {
+            Interval::new(0.0, self.upper.max(-self.lower))
+        } else {
+            Interval::new(
+                self.lower.abs().min(self.upper.abs()),
+                self.lower.abs().max(self.upper.abs()),
+            )
+        }
+    }
+
+    /// Applies function, which is increasing on the domain `[from, to]`.
+    fn increasing(
+        &self,
+        function: Function,
+        f: impl Fn(f64) -> f64,
+        (from, to): (f64, f64),
+    ) -> Result<Interval, NumberError> {
+        if self.lower < from || self.upper > to {
+            return Err(Interval::undefined(format!("{}({})", function, self)));
+        }
+
+        Ok(Interval::approximate(f(self.lower), f(self.upper)))
+    }
+
+    /// Tells whether the interval contains `phase + k * period` for some integer `k`.
+    /// It's conservative, so points close to the bounds are considered to be inside.
+    fn contains_periodic(&self, phase: f64, period: f64) -> bool {
+        const SLACK: f64 = 1e-9;
+
+        let lower = (self.lower - phase) / period - SLACK;
+        let upper = (self.upper - phase) / period + SLACK;
+        lower.ceil() <= upper
+    }
+
+    /// Sine or cosine, which has maximum at `phase` and minimum half period later.
+    fn wave(&self, f: impl Fn(f64) -> f64, phase: f64) -> Interval {
+        // Far from zero the phase can't be located precisely
+        if self.upper - self.lower >= 2.0 * PI || self.lower.abs().max(self.upper.abs()) > 1e9 {
+            return Interval::new(-1.0, 1.0);
+        }
+
+        let (first, second) = (f(self.lower), f(self.upper));
+        let bounds = Interval::approximate(first.min(second), first.max(second));
+
+        Interval::new(
+            if self.contains_periodic(phase + PI, 2.0 * PI) {
+                -1.0
+            } else {
+                bounds.lower.max(-1.0)
+            },
+            if self.contains_periodic(phase, 2.0 * PI) {
+                1.0
+            } else {
+                bounds.upper.min(1.0)
+            },
+        )
+    }
+
+    /// Non-negative integer power, which is computed by repeated multiplication.
+    fn powi(&self, exponent: u64) -> Result<Interval, NumberError> {
+        let power = |base: f64| -> Result<Interval, NumberError> {
+            let mut result = Interval::point(1.0);
+            let mut base = Interval::point(base);
+            let mut exponent = exponent;
+            while exponent > 0 {
+                if exponent % 2 == 1 {
+                    result = result.mul(&base)?;
+                }
+                base = base.mul(&base)?;
+                exponent /= 2;
+            }
+            Ok(result)
+        };
+
+        let bounds = if exponent % 2 == 0 {
+            self.magnitude()
+        } else {
+            *self
+        };
+
+        Ok(Interval::new(
+            power(bounds.lower)?.lower,
+            power(bounds.upper)?.upper,
+        ))
+    }
+
+    /// Guaranteed decimal bounds with `digits` digits after the point.
+    pub fn to_decimal(&self, digits: usize) -> String {
+        let bound =
+            |value: f64, round: fn(&BigRational) -> BigRational| match BigRational::from_float(
+                value,
+            ) {
+                Some(value) => Rational(value).to_decimal_rounded(digits, round),
+                None => value.to_string(),
+            };
+
+        format!(
+            "[{}, {}]",
+            bound(self.lower, BigRational::floor),
+            bound(self.upper, BigRational::ceil)
+        )
+    }
+
+    fn undefined(operation: impl fmt::Display) -> NumberError {
+        NumberError::Undefined {
+            operation: operation.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "[{}, {}]", self.lower, self.upper)
+    }
+}
+
+impl Number for Interval {
+    /// Literal is enclosed by the nearest `f64` values, unless it's exact.
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Decimal(text) => {
+                let invalid = || NumberError::InvalidLiteral {
+                    literal: text.clone(),
+                };
+                let exact = parse_decimal(text, MAX_EXPONENT)?;
+                let value = text.parse::<f64>().map_err(|_| invalid())?;
+
+                Ok(match BigRational::from_float(value) {
+                    Some(nearest) if nearest < exact => Interval::new(value, next_up(value)),
+                    Some(nearest) if nearest > exact => Interval::new(next_down(value), value),
+                    Some(_) => Interval::point(value),
+                    // Literal is too large
+                    None => Interval::new(std::f64::MAX, std::f64::INFINITY),
+                })
+            }
+            Literal::Constant(name) => match constants::lookup(name).unwrap() {
+                value if value.is_nan() => Err(Interval::undefined(format!("constant {}", name))),
+                value if value.is_infinite() => Ok(Interval::point(value)),
+                value => Ok(Interval::new(next_down(value), next_up(value))),
+            },
+            Literal::Imaginary(_) => Err(NumberError::Unsupported {
+                operation: format!("imaginary number {}", literal),
+            }),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        Ok(Interval::point(value))
+    }
+
+    /// Midpoint of the interval.
+    fn to_f64(&self) -> f64 {
+        self.lower / 2.0 + self.upper / 2.0
+    }
+
+    /// Bounds are rounded outward only in fixed notation.
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            Notation::Fixed(digits) => self.to_decimal(digits),
+            notation => format!(
+                "[{}, {}]",
+                notation.format_f64(self.lower),
+                notation.format_f64(self.upper)
+            ),
+        }
+    }
+
+    /// Overlapping intervals aren't ordered, since the result depends on the unknown values.
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        if self.upper < other.lower {
+            Ok(Some(Ordering::Less))
+        } else if self.lower > other.upper {
+            Ok(Some(Ordering::Greater))
+        } else if self.lower == self.upper && other.lower == other.upper {
+            Ok(Some(Ordering::Equal))
+        } else {
+            Err(Interval::undefined(format!(
+                "comparison of overlapping {} and {}",
+                self, other
+            )))
+        }
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(Interval::new(-self.upper, -self.lower))
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        let result = Interval::new(
+            add(self.lower, other.lower).0,
+            add(self.upper, other.upper).1,
+        );
+
+        if result.lower.is_nan() || result.upper.is_nan() {
+            Err(Interval::undefined(format!("{} + {}", self, other)))
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        self.add(&other.neg()?)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        let products = [
+            mul(self.lower, other.lower),
+            mul(self.lower, other.upper),
+            mul(self.upper, other.lower),
+            mul(self.upper, other.upper),
+        ];
+
+        if products
+            .iter()
+            .any(|(lower, upper)| lower.is_nan() || upper.is_nan())
+        {
+            return Err(Interval::undefined(format!("{} * {}", self, other)));
+        }
+
+        Ok(Interval::new(
+            products
+                .iter()
+                .map(|bounds| bounds.0)
+                .fold(std::f64::INFINITY, f64::min),
+            products
+                .iter()
+                .map(|bounds| bounds.1)
+                .fold(std::f64::NEG_INFINITY, f64::max),
+        ))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        if other.contains_zero() {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        let quotients = [
+            div(self.lower, other.lower),
+            div(self.lower, other.upper),
+            div(self.upper, other.lower),
+            div(self.upper, other.upper),
+        ];
+
+        if quotients
+            .iter()
+            .any(|(lower, upper)| lower.is_nan() || upper.is_nan())
+        {
+            return Err(Interval::undefined(format!("{} / {}", self, other)));
+        }
+
+        Ok(Interval::new(
+            quotients
+                .iter()
+                .map(|bounds| bounds.0)
+                .fold(std::f64::INFINITY, f64::min),
+            quotients
+                .iter()
+                .map(|bounds| bounds.1)
+                .fold(std::f64::NEG_INFINITY, f64::max),
+        ))
+    }
+
+    /// Integer exponents are allowed for any base, other ones only for positive bases.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        let exponent = other.lower;
+        if other.lower == other.upper && exponent.fract() == 0.0 && exponent.abs() < 1e18 {
+            return if exponent < 0.0 {
+                Interval::point(1.0).div(&self.powi(-exponent as u64)?)
+            } else {
+                self.powi(exponent as u64)
+            };
+        }
+
+        if self.lower <= 0.0 {
+            return Err(Interval::undefined(format!("{} ^ {}", self, other)));
+        }
+
+        Interval::call(
+            Function::Exp,
+            &[other.mul(&Interval::call(Function::Ln, &[*self])?)?],
+        )
+    }
+
+    fn interval(lower: &Self, upper: &Self) -> Result<Self, NumberError> {
+        Ok(Interval::new(
+            lower.lower.min(upper.lower),
+            lower.upper.max(upper.upper),
+        ))
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        let x = arguments[0];
+        let real = (std::f64::NEG_INFINITY, std::f64::INFINITY);
+        let positive = (0.0, std::f64::INFINITY);
+        let unit = (-1.0, 1.0);
+
+        match function {
+            Function::Sin => Ok(x.wave(f64::sin, PI / 2.0)),
+            Function::Cos => Ok(x.wave(f64::cos, 0.0)),
+            Function::Tan => {
+                if x.contains_periodic(PI / 2.0, PI) {
+                    Err(Interval::undefined(format!("{}({})", function, x)))
+                } else {
+                    x.increasing(function, f64::tan, real)
+                }
+            }
+            Function::Asin => x.increasing(function, f64::asin, unit),
+            // Decreasing function
+            Function::Acos if x.lower >= -1.0 && x.upper <= 1.0 => {
+                Ok(Interval::approximate(x.upper.acos(), x.lower.acos()))
+            }
+            Function::Acos => Err(Interval::undefined(format!("{}({})", function, x))),
+            Function::Atan => x.increasing(function, f64::atan, real),
+            Function::Sinh => x.increasing(function, f64::sinh, real),
+            Function::Cosh => x.magnitude().increasing(function, f64::cosh, real),
+            Function::Tanh => x.increasing(function, f64::tanh, real),
+            Function::Sqrt if x.lower >= 0.0 => Ok(Interval::new(sqrt(x.lower).0, sqrt(x.upper).1)),
+            Function::Sqrt => Err(Interval::undefined(format!("{}({})", function, x))),
+            Function::Cbrt => x.increasing(function, f64::cbrt, real),
+            Function::Exp => Ok(x
+                .increasing(function, f64::exp, real)?
+                .max(&Interval::point(0.0))),
+            Function::Ln => x.increasing(function, f64::ln, positive),
+            Function::Log2 => x.increasing(function, f64::log2, positive),
+            Function::Log10 => x.increasing(function, f64::log10, positive),
+            Function::Pow => x.pow(&arguments[1]),
+            Function::Hypot => {
+                let (x, y) = (x.magnitude(), arguments[1].magnitude());
+                Ok(
+                    Interval::approximate(x.lower.hypot(y.lower), x.upper.hypot(y.upper))
+                        .max(&Interval::point(0.0)),
+                )
+            }
+            Function::Abs => Ok(x.magnitude()),
+            Function::Floor => Ok(Interval::new(x.lower.floor(), x.upper.floor())),
+            Function::Ceil => Ok(Interval::new(x.lower.ceil(), x.upper.ceil())),
+            Function::Round => Ok(Interval::new(x.lower.round(), x.upper.round())),
+            Function::Min => Ok(x.min(&arguments[1])),
+            Function::Max => Ok(x.max(&arguments[1])),
+            Function::Atan2 | Function::Gamma | Function::Combinations | Function::Permutations => {
+                Err(NumberError::Unsupported {
+                    operation: format!("function {}", function),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::{Interpreter, InterpreterError};
+    use crate::Error;
+
+    fn exec(s: &str) -> Result<Interval, Error> {
+        Interpreter::exec(s, &mut Environment::<Interval>::default()).map(Option::unwrap)
+    }
+
+    fn contains(interval: Interval, exact: &str) -> bool {
+        let exact = parse_decimal(exact, MAX_EXPONENT).unwrap();
+        BigRational::from_float(interval.lower).unwrap() <= exact
+            && exact <= BigRational::from_float(interval.upper).unwrap()
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(exec("[1, 2] - [1, 2]").unwrap().to_string(), "[-1, 1]");
+        assert_eq!(exec("[-2, 3] ^ 2").unwrap().to_string(), "[0, 9]");
+        assert_eq!(exec("sqrt([4, 9])").unwrap().to_string(), "[2, 3]");
+        assert_eq!(exec("2 ± 0.5").unwrap().to_string(), "[1.5, 2.5]");
+        assert_eq!(exec("[2, 1] * 2").unwrap().to_string(), "[2, 4]");
+        assert_eq!(exec("2 * 3±1").unwrap().to_string(), "[4, 8]");
+        assert_eq!(
+            exec("[1, 2] < [3, 4] ? 5 : 6").unwrap().to_string(),
+            "[5, 5]"
+        );
+    }
+
+    #[test]
+    fn test_outward() {
+        let result = exec("3 * 0.1").unwrap();
+        assert!(result.lower < result.upper);
+        assert!(contains(result, "0.3"));
+        assert!(contains(exec("1 / 3 * 3").unwrap(), "1"));
+        assert!(contains(exec("5±0.1 - 0.2").unwrap(), "4.7"));
+        assert_eq!(
+            exec("[1.9, 2.1] * 2").unwrap().to_decimal(3),
+            "[3.799, 4.201]"
+        );
+
+        let sine = exec("sin([0, 4])").unwrap();
+        assert_eq!(sine.upper, 1.0);
+        assert!(sine.lower <= 4f64.sin());
+    }
+
+    #[test]
+    fn test_errors() {
+        match exec("1 / [-1, 1]").unwrap_err() {
+            Error::InterpreterError(InterpreterError::Arithmetic {
+                source: NumberError::DivisionByZero,
+                ..
+            }) => {}
+            e => panic!("Unexpected error {:?}", e),
+        }
+        assert!(exec("sqrt([-1, 1])").is_err());
+        assert!(exec("[1, 2, 3]").is_err());
+        assert!(exec("[1, 3] > 2").is_err());
+        assert!(Interpreter::exec("5±0.1", &mut Environment::new()).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Numeric types, which interpreter is able to evaluate expressions over.
+
+mod complex;
+mod decimal;
+mod float;
+mod integer;
+mod interval;
+mod quantity;
+mod rational;
+
+pub use complex::Complex;
+pub use decimal::{Context, Decimal, Rounding, MAX_PRECISION};
+pub use integer::Integer;
+pub use interval::Interval;
+pub use quantity::Quantity;
+pub use rational::Rational;
+
+use crate::constants;
+use crate::format::Notation;
+use crate::functions::Function;
+use crate::units::Unit;
+use snafu::Snafu;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Snafu, Debug, Clone, PartialEq)]
+pub enum NumberError {
+    #[snafu(display("Invalid number literal {}", literal))]
+    InvalidLiteral { literal: String },
+
+    #[snafu(display("Division by zero"))]
+    DivisionByZero,
+
+    #[snafu(display("{} doesn't have exact result", operation))]
+    NotExact { operation: String },
+
+    #[snafu(display("{} is undefined", operation))]
+    Undefined { operation: String },
+
+    #[snafu(display("{} isn't supported by this number type", operation))]
+    Unsupported { operation: String },
+
+    #[snafu(display("{} overflows", operation))]
+    Overflow { operation: String },
+
+    #[snafu(display("{} isn't an integer", value))]
+    NotInteger { value: String },
+
+    #[snafu(display("{} and {} have different dimensions", left, right))]
+    DimensionMismatch { left: String, right: String },
+}
+
+/// Numeric literal, which is kept as written, so every number type converts it without loss.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// Decimal number, e.g. `1.5e3`, to which integers in other radixes are converted.
+    Decimal(String),
+    /// Named constant, e.g. `pi`.
+    Constant(&'static str),
+    /// Imaginary number with decimal coefficient, e.g. `2.5i`.
+    Imaginary(String),
+}
+
+impl Literal {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Literal::Decimal(text) => text.parse().unwrap_or(std::f64::NAN),
+            Literal::Constant(name) => constants::lookup(name).unwrap(),
+            // Imaginary numbers aren't real, unless they are zero
+            Literal::Imaginary(text) => match text.parse::<f64>() {
+                Ok(coefficient) if coefficient == 0.0 => 0.0,
+                _ => std::f64::NAN,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Literal::Decimal(text) => write!(f, "{}", text),
+            Literal::Constant(_) => write!(f, "{}", self.to_f64()),
+            Literal::Imaginary(text) if text == "1" => write!(f, "i"),
+            Literal::Imaginary(text) => write!(f, "{}i", text),
+        }
+    }
+}
+
+/// Arithmetic of numeric type, every operation may fail instead of producing special value.
+pub trait Number: Clone + fmt::Debug + fmt::Display + Send + Sync + 'static {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError>;
+
+    fn from_f64(value: f64) -> Result<Self, NumberError>;
+
+    fn to_f64(&self) -> f64;
+
+    /// Text of the value in the given notation, where shortest notation is the same as `Display`.
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            notation => notation.format_f64(self.to_f64()),
+        }
+    }
+
+    fn neg(&self) -> Result<Self, NumberError>;
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError>;
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError>;
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError>;
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError>;
+
+    fn pow(&self, other: &Self) -> Result<Self, NumberError>;
+
+    /// Quotient rounded toward negative infinity.
+    fn floor_div(&self, other: &Self) -> Result<Self, NumberError> {
+        Self::call(Function::Floor, &[self.div(other)?])
+    }
+
+    /// Remainder of floor division, so `x == (x // y) * y + x % y`.
+    fn rem(&self, other: &Self) -> Result<Self, NumberError> {
+        self.sub(&other.mul(&self.floor_div(other)?)?)
+    }
+
+    /// Factorial, which is extended to non-integers as `gamma(x + 1)`.
+    fn factorial(&self) -> Result<Self, NumberError> {
+        Self::call(Function::Gamma, &[self.add(&Self::from_f64(1.0)?)?])
+    }
+
+    /// Order of values for comparison operators, `None` if they are unordered like NaN.
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(self.to_f64().partial_cmp(&other.to_f64()))
+    }
+
+    /// Booleans are represented by numbers `1` and `0`.
+    fn from_bool(value: bool) -> Result<Self, NumberError> {
+        Self::from_f64(if value { 1.0 } else { 0.0 })
+    }
+
+    /// Every value except zero is true in conditions.
+    fn is_true(&self) -> Result<bool, NumberError> {
+        Ok(self.compare(&Self::from_bool(false)?)? != Some(Ordering::Equal))
+    }
+
+    /// Bitwise operations are defined for integers, by default they are evaluated over `Integer`.
+    fn bit_and(&self, other: &Self) -> Result<Self, NumberError> {
+        bitwise(self, other, Integer::bit_and)
+    }
+
+    fn bit_or(&self, other: &Self) -> Result<Self, NumberError> {
+        bitwise(self, other, Integer::bit_or)
+    }
+
+    fn bit_xor(&self, other: &Self) -> Result<Self, NumberError> {
+        bitwise(self, other, Integer::bit_xor)
+    }
+
+    fn bit_not(&self) -> Result<Self, NumberError> {
+        Self::from_f64(Integer::from_f64(self.to_f64())?.bit_not()?.to_f64())
+    }
+
+    fn shl(&self, other: &Self) -> Result<Self, NumberError> {
+        bitwise(self, other, Integer::shl)
+    }
+
+    fn shr(&self, other: &Self) -> Result<Self, NumberError> {
+        bitwise(self, other, Integer::shr)
+    }
+
+    /// Attaches unit to the value of a literal like `3 km`.
+    fn with_unit(&self, unit: &Unit) -> Result<Self, NumberError> {
+        Err(NumberError::Unsupported {
+            operation: format!("unit {}", unit),
+        })
+    }
+
+    /// Expresses value in another unit of the same dimension.
+    fn convert(&self, unit: &Unit) -> Result<Self, NumberError> {
+        Err(NumberError::Unsupported {
+            operation: format!("conversion to {}", unit),
+        })
+    }
+
+    /// Smallest interval, which contains both values.
+    fn interval(lower: &Self, upper: &Self) -> Result<Self, NumberError> {
+        Err(NumberError::Unsupported {
+            operation: format!("interval [{}, {}]", lower, upper),
+        })
+    }
+
+    /// Calls built-in function, by default it's evaluated over `f64`.
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        Self::from_f64(function.call(&arguments.iter().map(Number::to_f64).collect::<Vec<_>>()))
+    }
+}
+
+/// Largest argument of combinatorial functions, which are computed exactly.
+const MAX_EXACT_COMBINATORICS: f64 = 10_000.0;
+
+/// Computes gamma, `nCr` and `nPr` of non-negative integers in the number type itself, so they're
+/// exact in exact modes. Returns `None` for other functions and arguments.
+fn exact_combinatorics<N: Number>(
+    function: Function,
+    arguments: &[N],
+) -> Option<Result<N, NumberError>> {
+    let integers = arguments
+        .iter()
+        .map(|x| {
+            // Integers are compared in the number type, since `f64` rounds off e.g. `5 + 10^-20`
+            let value = x.to_f64().trunc();
+            match N::from_f64(value).and_then(|integer| x.compare(&integer)) {
+                Ok(Some(Ordering::Equal)) if (0.0..=MAX_EXACT_COMBINATORICS).contains(&value) => {
+                    Some(value as u64)
+                }
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let product = |from: u64, to: u64| {
+        (from..=to).try_fold(N::from_f64(1.0)?, |product, i| {
+            product.mul(&N::from_f64(i as f64)?)
+        })
+    };
+
+    match (function, integers.as_slice()) {
+        (Function::Gamma, [n]) if *n > 0 => Some(product(1, n - 1)),
+        (Function::Combinations, [n, k]) | (Function::Permutations, [n, k]) if k > n => {
+            Some(N::from_f64(0.0))
+        }
+        (Function::Combinations, [n, k]) => {
+            let k = (*k).min(n - k);
+            Some(product(n - k + 1, *n).and_then(|numerator| numerator.div(&product(1, k)?)))
+        }
+        (Function::Permutations, [n, k]) => Some(product(n - k + 1, *n)),
+        _ => None,
+    }
+}
+
+/// Evaluates bitwise operation over values converted to integers.
+fn bitwise<N: Number>(
+    x: &N,
+    y: &N,
+    operation: impl Fn(&Integer, &Integer) -> Result<Integer, NumberError>,
+) -> Result<N, NumberError> {
+    let x = Integer::from_f64(x.to_f64())?;
+    let y = Integer::from_f64(y.to_f64())?;
+
+    N::from_f64(operation(&x, &y)?.to_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(Literal::Decimal("1.5e3".to_owned()).to_f64(), 1500.0);
+        assert_eq!(Literal::Decimal("0.10".to_owned()).to_string(), "0.10");
+        assert_eq!(
+            Literal::Constant("pi").to_string(),
+            std::f64::consts::PI.to_string()
+        );
+        assert_eq!(Literal::Imaginary("1".to_owned()).to_string(), "i");
+        assert!(f64::from_literal(&Literal::Imaginary("2".to_owned())).is_err());
+    }
+}
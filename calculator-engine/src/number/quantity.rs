@@ -0,0 +1,319 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::{Literal, Number, NumberError};
+use crate::format::Notation;
+use crate::functions::Function;
+use crate::units::Unit;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Floating point value with unit, in which it's expressed, e.g. `3.2 km`.
+///
+/// Units of results are kept as written, so `3 km + 200 m` is `3.2 km`,
+/// while dimensionless results like `3 km / 200 m` are plain numbers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    /// Creates quantity, which is converted to plain number, when its unit is dimensionless.
+    fn new(value: f64, unit: Unit) -> Quantity {
+        if unit.dimension().is_dimensionless() {
+            Quantity::plain(value * unit.factor())
+        } else {
+            Quantity { value, unit }
+        }
+    }
+
+    fn plain(value: f64) -> Quantity {
+        Quantity {
+            value,
+            unit: Unit::default(),
+        }
+    }
+
+    fn is_dimensionless(&self) -> bool {
+        self.unit.dimension().is_dimensionless()
+    }
+
+    /// Value in SI base units.
+    fn base_value(&self) -> f64 {
+        self.value * self.unit.factor()
+    }
+
+    /// Value expressed in the unit of `other`, which must have the same dimension.
+    fn value_in_unit_of(&self, other: &Quantity) -> Result<f64, NumberError> {
+        if self.unit.dimension() == other.unit.dimension() {
+            Ok(self.base_value() / other.unit.factor())
+        } else {
+            Err(NumberError::DimensionMismatch {
+                left: other.describe_unit(),
+                right: self.describe_unit(),
+            })
+        }
+    }
+
+    fn describe_unit(&self) -> String {
+        if self.unit.is_empty() {
+            "plain number".to_owned()
+        } else {
+            self.unit.to_string()
+        }
+    }
+
+    /// Value with `digits` digits after the point followed by unit.
+    pub fn to_decimal(&self, digits: usize) -> String {
+        if self.unit.is_empty() {
+            format!("{:.*}", digits, self.value)
+        } else {
+            format!("{:.*} {}", digits, self.value, self.unit)
+        }
+    }
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.unit.is_empty() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} {}", self.value, self.unit)
+        }
+    }
+}
+
+impl Number for Quantity {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Imaginary(_) => Err(NumberError::Unsupported {
+                operation: format!("imaginary number {}", literal),
+            }),
+            literal => Ok(Quantity::plain(literal.to_f64())),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        Ok(Quantity::plain(value))
+    }
+
+    /// Only dimensionless quantities are numbers.
+    fn to_f64(&self) -> f64 {
+        if self.is_dimensionless() {
+            self.base_value()
+        } else {
+            std::f64::NAN
+        }
+    }
+
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            Notation::Fixed(digits) => self.to_decimal(digits),
+            notation if self.unit.is_empty() => notation.format_f64(self.value),
+            notation => format!("{} {}", notation.format_f64(self.value), self.unit),
+        }
+    }
+
+    /// Quantities are compared in the same unit, so `1 km > 900 m`.
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(self.value.partial_cmp(&other.value_in_unit_of(self)?))
+    }
+
+    /// Zero is false in every unit.
+    fn is_true(&self) -> Result<bool, NumberError> {
+        Ok(self.value != 0.0)
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(Quantity {
+            value: -self.value,
+            unit: self.unit.clone(),
+        })
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Quantity {
+            value: self.value + other.value_in_unit_of(self)?,
+            unit: self.unit.clone(),
+        })
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Quantity {
+            value: self.value - other.value_in_unit_of(self)?,
+            unit: self.unit.clone(),
+        })
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Quantity::new(
+            self.value * other.value,
+            self.unit.mul(&other.unit),
+        ))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Quantity::new(
+            self.value / other.value,
+            self.unit.mul(&other.unit.powi(-1)),
+        ))
+    }
+
+    /// Quantity with dimension may be raised only to integer power.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        let exponent = other.to_f64();
+
+        if !other.is_dimensionless() {
+            Err(NumberError::Undefined {
+                operation: format!("{} ^ {}", self, other),
+            })
+        } else if self.is_dimensionless() {
+            Ok(Quantity::plain(self.base_value().powf(exponent)))
+        } else if exponent.fract() == 0.0 && exponent.abs() <= f64::from(std::i32::MAX) {
+            let exponent = exponent as i32;
+            Ok(Quantity::new(
+                self.value.powi(exponent),
+                self.unit.powi(exponent),
+            ))
+        } else {
+            Err(NumberError::Undefined {
+                operation: format!("{} ^ {}", self, other),
+            })
+        }
+    }
+
+    fn with_unit(&self, unit: &Unit) -> Result<Self, NumberError> {
+        Ok(Quantity::new(self.base_value(), unit.clone()))
+    }
+
+    fn convert(&self, unit: &Unit) -> Result<Self, NumberError> {
+        if self.unit.dimension() == unit.dimension() {
+            Ok(Quantity {
+                value: self.base_value() / unit.factor(),
+                unit: unit.clone(),
+            })
+        } else {
+            Err(NumberError::DimensionMismatch {
+                left: self.describe_unit(),
+                right: unit.to_string(),
+            })
+        }
+    }
+
+    /// Functions keep the unit, when it makes sense, otherwise argument must be dimensionless.
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        let root = |x: &Quantity, degree| match x.unit.root(degree) {
+            Some(unit) => Ok(Quantity::new(function.call(&[x.value]), unit)),
+            None => Err(NumberError::Undefined {
+                operation: format!("function {} of {}", function, x.unit),
+            }),
+        };
+
+        match (function, arguments) {
+            (Function::Abs, [x])
+            | (Function::Floor, [x])
+            | (Function::Ceil, [x])
+            | (Function::Round, [x]) => Ok(Quantity {
+                value: function.call(&[x.value]),
+                unit: x.unit.clone(),
+            }),
+            (Function::Min, [x, y]) | (Function::Max, [x, y]) | (Function::Hypot, [x, y]) => {
+                Ok(Quantity {
+                    value: function.call(&[x.value, y.value_in_unit_of(x)?]),
+                    unit: x.unit.clone(),
+                })
+            }
+            (Function::Atan2, [x, y]) => Ok(Quantity::plain(
+                function.call(&[x.value, y.value_in_unit_of(x)?]),
+            )),
+            (Function::Sqrt, [x]) => root(x, 2),
+            (Function::Cbrt, [x]) => root(x, 3),
+            (Function::Pow, [x, y]) => x.pow(y),
+            (function, arguments) => match arguments.iter().find(|x| !x.is_dimensionless()) {
+                Some(x) => Err(NumberError::Undefined {
+                    operation: format!("function {} of {}", function, x.unit),
+                }),
+                None => Ok(Quantity::plain(
+                    function.call(&arguments.iter().map(Number::to_f64).collect::<Vec<_>>()),
+                )),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::{Interpreter, InterpreterError};
+    use crate::syntax::Syntax;
+    use crate::Error;
+
+    fn exec(s: &str) -> Result<String, Error> {
+        let units = Syntax {
+            units: true,
+            ..Syntax::default()
+        };
+        units
+            .scope(|| Interpreter::exec(s, &mut Environment::<Quantity>::default()))
+            .map(|result| result.unwrap().to_string())
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(exec("3 km + 200 m").unwrap(), "3.2 km");
+        assert_eq!(exec("60 mph in m/s").unwrap(), "26.8224 m/s");
+        assert_eq!(exec("3 km / 200 m").unwrap(), "15");
+        assert_eq!(exec("2 m * 3 m").unwrap(), "6 m^2");
+        assert_eq!(exec("sqrt(16 m^2) to cm").unwrap(), "400 cm");
+        assert_eq!(exec("9.5 kg*m/s^2 in N").unwrap(), "9.5 N");
+        assert_eq!(exec("7 m % 2 m").unwrap(), "1 m");
+        assert_eq!(exec("1 km > 900 m and 0 m == 0 cm").unwrap(), "1");
+        // Bound variables and function calls aren't units
+        assert_eq!(exec("t = 2; 3t + 1 t").unwrap(), "8");
+        assert_eq!(exec("f(s) = 2s; f(3 s)").unwrap(), "6 s");
+        assert_eq!(exec("2 min(3, 4)").unwrap(), "6");
+    }
+
+    #[test]
+    fn test_errors() {
+        match exec("1 + (2 m + 3 s)").unwrap_err() {
+            Error::InterpreterError(InterpreterError::Arithmetic {
+                source: NumberError::DimensionMismatch { left, right },
+                span,
+            }) => {
+                assert_eq!((left.as_str(), right.as_str()), ("m", "s"));
+                assert_eq!((span.start, span.end), (5, 14));
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        assert!(exec("5 in m").is_err());
+        assert!(exec("sin(2 m)").is_err());
+        assert!(exec("sqrt(2 m)").is_err());
+        assert!(exec("2 ^ (3 s)").is_err());
+    }
+}
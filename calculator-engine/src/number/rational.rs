@@ -0,0 +1,330 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use super::{exact_combinatorics, Literal, Number, NumberError};
+use crate::format::Notation;
+use crate::functions::Function;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{pow, One, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Exact rational number with arbitrary-precision numerator and denominator.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rational(pub BigRational);
+
+impl Rational {
+    /// Decimal expansion with `digits` digits after the point, rounded half away from zero.
+    pub fn to_decimal(&self, digits: usize) -> String {
+        self.to_decimal_rounded(digits, BigRational::round)
+    }
+
+    /// Decimal expansion with `digits` digits after the point, where `round` rounds to an integer.
+    pub(super) fn to_decimal_rounded(
+        &self,
+        digits: usize,
+        round: impl Fn(&BigRational) -> BigRational,
+    ) -> String {
+        let scaled = round(&(&self.0 * BigRational::from_integer(pow(BigInt::from(10), digits))));
+        let magnitude = scaled.to_integer().abs().to_string();
+        let sign = if scaled.is_negative() { "-" } else { "" };
+
+        if digits == 0 {
+            return format!("{}{}", sign, magnitude);
+        }
+
+        let magnitude = format!("{:0>width$}", magnitude, width = digits + 1);
+        let (integer, fraction) = magnitude.split_at(magnitude.len() - digits);
+
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+
+    /// Root of the given degree, when it's rational.
+    fn root(&self, degree: u32) -> Option<Rational> {
+        if degree % 2 == 0 && self.0.is_negative() {
+            return None;
+        }
+
+        let root = |value: &BigInt| {
+            let root = value.nth_root(degree);
+            if pow(root.clone(), degree as usize) == *value {
+                Some(root)
+            } else {
+                None
+            }
+        };
+
+        Some(Rational(BigRational::new(
+            root(self.0.numer())?,
+            root(self.0.denom())?,
+        )))
+    }
+
+    fn not_exact(operation: impl fmt::Display) -> NumberError {
+        NumberError::NotExact {
+            operation: operation.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Rational {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Largest decimal exponent of exact literals, larger powers of ten take too long to compute.
+pub(super) const MAX_EXPONENT: i64 = 10_000;
+
+/// Largest number of bits of numerator and denominator of exact powers.
+const MAX_POWER_BITS: usize = 100_000;
+
+/// Parses decimal literal like `1.25e-3` without loss of precision.
+/// Literals, which are scaled by more than `max_exponent` powers of ten, overflow.
+pub(super) fn parse_decimal(text: &str, max_exponent: i64) -> Result<BigRational, NumberError> {
+    let invalid = || NumberError::InvalidLiteral {
+        literal: text.to_owned(),
+    };
+
+    let (mantissa, exponent) = match text.find(|c| c == 'e' || c == 'E') {
+        Some(position) => (
+            &text[..position],
+            text[position + 1..].parse::<i64>().map_err(|_| invalid())?,
+        ),
+        None => (text, 0),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(position) => (&mantissa[..position], &mantissa[position + 1..]),
+        None => (mantissa, ""),
+    };
+
+    let numerator = BigInt::parse_bytes(format!("{}{}", integer, fraction).as_bytes(), 10)
+        .ok_or_else(invalid)?;
+    if numerator.is_zero() {
+        return Ok(BigRational::zero());
+    }
+
+    let exponent = exponent.saturating_sub(fraction.len() as i64);
+    if exponent.abs() > max_exponent {
+        return Err(NumberError::Overflow {
+            operation: format!("literal {}", text),
+        });
+    }
+    let scale = pow(BigInt::from(10), exponent.abs() as usize);
+
+    Ok(if exponent >= 0 {
+        BigRational::from_integer(numerator * scale)
+    } else {
+        BigRational::new(numerator, scale)
+    })
+}
+
+impl Number for Rational {
+    fn from_literal(literal: &Literal) -> Result<Self, NumberError> {
+        match literal {
+            Literal::Decimal(text) => parse_decimal(text, MAX_EXPONENT).map(Rational),
+            Literal::Constant(name) => Err(Rational::not_exact(format!("constant {}", name))),
+            Literal::Imaginary(_) => Err(NumberError::Unsupported {
+                operation: format!("imaginary number {}", literal),
+            }),
+        }
+    }
+
+    fn from_f64(value: f64) -> Result<Self, NumberError> {
+        BigRational::from_float(value)
+            .map(Rational)
+            .ok_or_else(|| Rational::not_exact(value))
+    }
+
+    fn to_f64(&self) -> f64 {
+        match (self.0.numer().to_f64(), self.0.denom().to_f64()) {
+            (Some(numerator), Some(denominator)) => numerator / denominator,
+            _ => std::f64::NAN,
+        }
+    }
+
+    /// Fixed notation is exact, while the others round the nearest `f64`.
+    fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Shortest => self.to_string(),
+            Notation::Fixed(digits) => self.to_decimal(digits),
+            notation => notation.format_f64(self.to_f64()),
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, NumberError> {
+        Ok(Some(self.0.cmp(&other.0)))
+    }
+
+    fn neg(&self) -> Result<Self, NumberError> {
+        Ok(Rational(-&self.0))
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Rational(&self.0 + &other.0))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Rational(&self.0 - &other.0))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, NumberError> {
+        Ok(Rational(&self.0 * &other.0))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, NumberError> {
+        if other.0.is_zero() {
+            Err(NumberError::DivisionByZero)
+        } else {
+            Ok(Rational(&self.0 / &other.0))
+        }
+    }
+
+    /// Power is exact for integer exponents, and for fractional ones when the root is rational.
+    fn pow(&self, other: &Self) -> Result<Self, NumberError> {
+        let not_exact = || Rational::not_exact(format!("{} ^ {}", self, other));
+
+        let numerator = other.0.numer().to_i32().ok_or_else(not_exact)?;
+        let degree = other.0.denom().to_u32().ok_or_else(not_exact)?;
+
+        if self.0.is_zero() && numerator < 0 {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        let base = self.root(degree).ok_or_else(not_exact)?;
+        let exponent = i64::from(numerator).abs() as usize;
+
+        // Powers of zero and one stay small
+        let bits = base.0.numer().bits() + base.0.denom().bits();
+        if !base.0.is_zero()
+            && !base.0.abs().is_one()
+            && bits.saturating_mul(exponent) > MAX_POWER_BITS
+        {
+            return Err(NumberError::Overflow {
+                operation: format!("{} ^ {}", self, other),
+            });
+        }
+        let power = pow(base.0, exponent);
+
+        Ok(Rational(if numerator < 0 { power.recip() } else { power }))
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, NumberError> {
+        let not_exact = || Rational::not_exact(format!("function {}", function));
+
+        if let Some(result) = exact_combinatorics(function, arguments) {
+            return result;
+        }
+
+        match (function, arguments) {
+            (Function::Abs, [x]) => Ok(Rational(x.0.abs())),
+            (Function::Floor, [x]) => Ok(Rational(x.0.floor())),
+            (Function::Ceil, [x]) => Ok(Rational(x.0.ceil())),
+            (Function::Round, [x]) => Ok(Rational(x.0.round())),
+            (Function::Min, [x, y]) => Ok(x.clone().min(y.clone())),
+            (Function::Max, [x, y]) => Ok(x.clone().max(y.clone())),
+            (Function::Pow, [x, y]) => x.pow(y),
+            (Function::Sqrt, [x]) => x.root(2).ok_or_else(not_exact),
+            (Function::Cbrt, [x]) => x.root(3).ok_or_else(not_exact),
+            (Function::Hypot, [x, y]) => x.mul(x)?.add(&y.mul(y)?)?.root(2).ok_or_else(not_exact),
+            _ => Err(not_exact()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::environment::Environment;
+    use crate::execution::interpret::{Interpreter, InterpreterError};
+    use crate::Error;
+
+    fn exec(s: &str) -> Result<Rational, Error> {
+        Interpreter::exec(s, &mut Environment::<Rational>::default()).map(Option::unwrap)
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(exec("1/3 + 1/6").unwrap().to_string(), "1/2");
+        assert_eq!(exec("0.1 + 0.2").unwrap().to_string(), "3/10");
+        assert_eq!(exec("1.5e-3 * 2e3").unwrap().to_string(), "3");
+        assert_eq!(exec("2 ^ -2 + 8 ^ (2 / 3)").unwrap().to_string(), "17/4");
+        assert_eq!(exec("sqrt(9 / 4) - abs(-1)").unwrap().to_string(), "1/2");
+        assert_eq!(
+            exec("25!").unwrap().to_string(),
+            "15511210043330985984000000"
+        );
+        assert_eq!(
+            exec("nCr(60, 30)").unwrap().to_string(),
+            "118264581564861424"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        match exec("1 + 1 / (2 - 2)").unwrap_err() {
+            Error::InterpreterError(InterpreterError::Arithmetic {
+                source: NumberError::DivisionByZero,
+                span,
+            }) => assert_eq!((span.start, span.end), (4, 15)),
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        assert!(exec("sqrt(2)").is_err());
+        assert!(exec("sin(1)").is_err());
+        assert!(exec("2 * pi").is_err());
+        assert!(exec("(5 + 10^-20)!").is_err());
+        assert!(exec("nCr(5 + 10^-20, 2)").is_err());
+
+        // Huge powers of ten and two are rejected instead of being computed
+        for source in &[
+            "1e999999999999",
+            "1e-999999999999",
+            "2 ^ 2000000000",
+            "(1/3) ^ -200000",
+        ] {
+            match exec(source).unwrap_err() {
+                Error::InterpreterError(InterpreterError::Arithmetic {
+                    source: NumberError::Overflow { .. },
+                    ..
+                }) => {}
+                e => panic!("Unexpected error {:?}", e),
+            }
+        }
+        assert_eq!(
+            exec("1 ^ 2000000000 + 0e999999999999").unwrap(),
+            exec("1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        let third = exec("-2/3").unwrap();
+        assert_eq!(third.to_decimal(3), "-0.667");
+        assert_eq!(third.to_decimal(0), "-1");
+        assert_eq!(exec("1/8").unwrap().to_decimal(5), "0.12500");
+        assert_eq!(exec("123/10").unwrap().to_decimal(1), "12.3");
+    }
+}
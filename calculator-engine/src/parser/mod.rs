@@ -22,18 +22,25 @@
  */
 
 mod errors;
+mod normalize;
 pub use errors::*;
 
+use crate::number::Literal;
+use crate::span::{Span, Spanned};
+use crate::syntax::Syntax;
+use crate::units::Unit;
 use derive_more::From;
 use nom::{
     branch::alt,
-    bytes::complete::take,
-    character::complete::{char, one_of},
-    combinator::map,
-    multi::{fold_many1, many0},
-    number::complete::double,
-    sequence::tuple,
+    bytes::complete::{tag, take, take_while, take_while1, take_while_m_n},
+    character::complete::{alpha1, alphanumeric1, char, digit1, one_of},
+    combinator::{map, not, opt, recognize},
+    error::ErrorKind,
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
 };
+use normalize::Normalized;
+use num_bigint::BigInt;
 use snafu::Snafu;
 use std::fmt;
 use std::fmt::Formatter;
@@ -45,13 +52,96 @@ pub enum Operator {
     Plus,
     Divide,
     Multiply,
+    Power,
+    /// Interval around the value, e.g. `5±0.1`.
+    PlusMinus,
+    /// Division rounded toward negative infinity, e.g. `-7 // 2` is `-4`.
+    FloorDivide,
+    /// Remainder of floor division, which has sign of the divisor, written as `%` or `mod`.
+    Remainder,
+    /// Percentage of value, e.g. `50% of 80`.
+    Of,
+    BitAnd,
+    BitOr,
+    /// Written as `xor`, because `^` is exponentiation.
+    BitXor,
+    /// Bitwise complement, which is only a prefix operator.
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+    /// Factorial `n!`, which is only a postfix operator.
+    Factorial,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    /// Logical operators evaluate the right operand only when it determines the result.
+    And,
+    Or,
+    /// Logical negation, which is only a prefix operator.
+    Not,
 }
 
 impl Operator {
+    /// Binding power of the infix operator, bitwise ones are ordered like in C,
+    /// while logical operators and comparisons bind looser like in Python.
     pub fn precedence(self) -> usize {
         match self {
-            Operator::Minus | Operator::Plus => 1,
-            Operator::Divide | Operator::Multiply => 2,
+            Operator::Or => 2,
+            Operator::And => 3,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => 5,
+            Operator::BitOr => 6,
+            Operator::BitXor => 7,
+            Operator::BitAnd => 8,
+            Operator::ShiftLeft | Operator::ShiftRight => 9,
+            Operator::Minus | Operator::Plus => 10,
+            Operator::Divide
+            | Operator::Multiply
+            | Operator::FloorDivide
+            | Operator::Remainder
+            | Operator::Of => 11,
+            Operator::BitNot | Operator::Not => self.unary_precedence(),
+            Operator::PlusMinus => 14,
+            Operator::Power => 15,
+            Operator::Factorial => 16,
+        }
+    }
+
+    /// Binding power of the operator, when it is used in prefix position.
+    /// It's placed between multiplication and exponentiation, so `-2^2` is `-(2^2)`,
+    /// while `not` applies to the whole comparison.
+    pub fn unary_precedence(self) -> usize {
+        match self {
+            Operator::Not => 4,
+            // Leaves room for tight implicit multiplication right above `*`
+            _ => 13,
+        }
+    }
+
+    /// Whether operator can be placed between operands.
+    pub fn is_infix(self) -> bool {
+        match self {
+            Operator::BitNot | Operator::Factorial | Operator::Not => false,
+            _ => true,
+        }
+    }
+
+    /// Whether operator follows its operand.
+    pub fn is_postfix(self) -> bool {
+        self == Operator::Factorial
+    }
+
+    pub fn is_right_associative(self) -> bool {
+        match self {
+            Operator::Power => true,
+            _ => false,
         }
     }
 }
@@ -62,10 +152,31 @@ impl std::fmt::Display for Operator {
             f,
             "{}",
             match self {
-                Operator::Minus => '-',
-                Operator::Plus => '+',
-                Operator::Divide => '/',
-                Operator::Multiply => '*',
+                Operator::Minus => "-",
+                Operator::Plus => "+",
+                Operator::Divide => "/",
+                Operator::Multiply => "*",
+                Operator::Power => "^",
+                Operator::PlusMinus => "±",
+                Operator::FloorDivide => "//",
+                Operator::Remainder => "%",
+                Operator::Of => "of",
+                Operator::BitAnd => "&",
+                Operator::BitOr => "|",
+                Operator::BitXor => "xor",
+                Operator::BitNot => "~",
+                Operator::ShiftLeft => "<<",
+                Operator::ShiftRight => ">>",
+                Operator::Factorial => "!",
+                Operator::Equal => "==",
+                Operator::NotEqual => "!=",
+                Operator::Less => "<",
+                Operator::LessEqual => "<=",
+                Operator::Greater => ">",
+                Operator::GreaterEqual => ">=",
+                Operator::And => "and",
+                Operator::Or => "or",
+                Operator::Not => "not",
             }
         )
     }
@@ -73,15 +184,40 @@ impl std::fmt::Display for Operator {
 
 #[derive(Clone, Debug)]
 pub enum Token {
-    Number(f64),
+    Number(Literal),
     Operator(Operator),
+    Identifier(String),
     OpenParenthesis,
     CloseParenthesis,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Assign,
+    /// Unit, which follows a number, e.g. `km` in `3 km`.
+    Unit(Unit),
+    /// Conversion to unit, e.g. `in m/s`.
+    Conversion(Unit),
+    /// Either remainder `7 % 2` or percentage `10%`, depending on whether operand follows it.
+    Percent,
+    /// Keywords and punctuation of conditional expressions `if c then a else b` and `c ? a : b`.
+    If,
+    Then,
+    Else,
+    Question,
+    Colon,
+    /// End of statement, either `;` or new line.
+    Separator,
 }
 impl Token {
     pub fn precedence(&self) -> usize {
         match self {
             Token::Operator(op) => op.precedence(),
+            Token::Percent => Operator::Remainder.precedence(),
+            // Binds as loosely as the loosest operator, so it converts the whole expression
+            Token::Conversion(_) => Operator::Or.precedence(),
+            Token::Question => 1,
+            // End the preceding part of conditional expression
+            Token::Then | Token::Else | Token::Colon => 0,
             _ => usize::max_value(),
         }
     }
@@ -90,10 +226,48 @@ impl Token {
 pub enum ParseUserError {
     #[snafu(display("Invalid operator: {}", operator))]
     InvalidOperator { operator: char },
+
+    #[snafu(display("Unexpected character: {}", character))]
+    UnexpectedCharacter { character: char, span: Span },
+
+    #[snafu(display("Empty expression"))]
+    EmptyInput,
+
+    #[snafu(display("Invalid digit in base {} number {}", radix, literal))]
+    InvalidDigit {
+        literal: String,
+        radix: u32,
+        span: Span,
+    },
 }
 fn parse_operator(s: &str) -> IResult<Operator> {
+    for (text, operator) in &[
+        ("**", Operator::Power),
+        ("//", Operator::FloorDivide),
+        ("<<", Operator::ShiftLeft),
+        (">>", Operator::ShiftRight),
+        ("±", Operator::PlusMinus),
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessEqual),
+        (">=", Operator::GreaterEqual),
+    ] {
+        if let Ok((s, _)) = tag::<_, _, ParseError<&str>>(*text)(s) {
+            return Ok((s, *operator));
+        }
+    }
+    // Keywords, unless they are the beginning of a longer identifier
+    match parse_identifier(s) {
+        Ok((s, "xor")) => return Ok((s, Operator::BitXor)),
+        Ok((s, "of")) => return Ok((s, Operator::Of)),
+        Ok((s, "mod")) => return Ok((s, Operator::Remainder)),
+        Ok((s, "and")) => return Ok((s, Operator::And)),
+        Ok((s, "or")) => return Ok((s, Operator::Or)),
+        Ok((s, "not")) => return Ok((s, Operator::Not)),
+        _ => {}
+    }
+
     let (s, c) = take(1 as usize)(s)?;
-    assert_eq!(c.len(), 1);
     Ok((
         s,
         match c.chars().next().unwrap() {
@@ -101,50 +275,346 @@ fn parse_operator(s: &str) -> IResult<Operator> {
             '-' => Ok(Operator::Minus),
             '*' => Ok(Operator::Multiply),
             '/' => Ok(Operator::Divide),
+            '^' => Ok(Operator::Power),
+            '!' => Ok(Operator::Factorial),
+            '<' => Ok(Operator::Less),
+            '>' => Ok(Operator::Greater),
+            '&' => Ok(Operator::BitAnd),
+            '|' => Ok(Operator::BitOr),
+            '~' => Ok(Operator::BitNot),
             operator => Err(nom::Err::Error(
                 ParseUserError::InvalidOperator { operator }.into(),
             )),
         }?,
     ))
 }
-fn parse_number(s: &str) -> IResult<f64> {
-    double(s)
+/// Digits of the given radix, which may be separated by single underscores, e.g. `1_000`.
+fn parse_digits(s: &str, radix: u32) -> IResult<&str> {
+    let digit = move |c: char| c.is_digit(radix);
+    recognize(pair(
+        take_while1(digit),
+        many0(pair(char('_'), take_while1(digit))),
+    ))(s)
 }
-fn skip_whitespace(s: &str) -> IResult<()> {
-    Ok((many0(one_of(" \t\x0c\n"))(s)?.0, ()))
-}
-pub fn parse(s: &str) -> IResult<Vec<Token>, ParseError> {
-    fold_many1(
-        map(
-            tuple((
-                skip_whitespace,
-                alt((
-                    map(parse_operator, Token::Operator),
-                    map(parse_number, Token::Number),
-                    map(char('('), |_| Token::OpenParenthesis),
-                    map(char(')'), |_| Token::CloseParenthesis),
-                )),
-            )),
-            |((), token)| token,
+/// Exactly three digits after grouping separator.
+fn parse_group(s: &str, grouping: char) -> IResult<&str> {
+    preceded(
+        char(grouping),
+        terminated(
+            take_while_m_n(3, 3, |c: char| c.is_ascii_digit()),
+            not(one_of("0123456789_")),
         ),
-        Vec::new(),
-        |mut acc, token| {
-            acc.push(token);
-            acc
-        },
     )(s)
-    .map_err(|e: nom::Err<ParseError<&str>>| {
-        let e: nom::Err<ParseError> = parse_error_to_owned(e);
-        e
-    })
+}
+/// Decimal digits, which may be also separated by grouping separator of the locale,
+/// e.g. `1.000.000` in German. Groups have three digits, so `1.5` isn't taken for grouped number.
+fn parse_grouped_digits(s: &str) -> IResult<&str> {
+    let (mut rest, _) = parse_digits(s, 10)?;
+
+    if let Some(grouping) = Syntax::current().locale.grouping {
+        while let Ok((after, _)) = parse_group(rest, grouping) {
+            rest = after;
+        }
+    }
+
+    Ok((rest, &s[..s.len() - rest.len()]))
+}
+/// Decimal number like `1_000.5e-3`, exponent is taken only when it has digits.
+/// Decimal and grouping separators are taken from the locale.
+fn recognize_decimal(s: &str) -> IResult<&str> {
+    let point = Syntax::current().locale.decimal;
+    let digits = |s| parse_digits(s, 10);
+    recognize(pair(
+        alt((
+            recognize(pair(
+                parse_grouped_digits,
+                opt(pair(char(point), opt(digits))),
+            )),
+            recognize(pair(char(point), digits)),
+        )),
+        opt(tuple((one_of("eE"), opt(one_of("+-")), digits))),
+    ))(s)
+}
+/// Integer with radix prefix like `0x1F`, `0o17` or `0b1010`, which is converted to decimal.
+/// Letters and digits right after it are invalid digits, so `0o19` isn't split into `0o1` and `9`.
+/// Span of the failure is relative to `s`.
+fn parse_radix_integer(s: &str) -> IResult<String> {
+    let (after_prefix, radix) = alt((
+        map(alt((tag("0x"), tag("0X"))), |_| 16),
+        map(alt((tag("0o"), tag("0O"))), |_| 8),
+        map(alt((tag("0b"), tag("0B"))), |_| 2),
+    ))(s)?;
+    let (rest, digits) = parse_digits(after_prefix, radix).unwrap_or((after_prefix, ""));
+
+    let invalid = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+    if digits.is_empty() || invalid > 0 {
+        let end = s.len() - rest.len() + invalid;
+        return Err(nom::Err::Failure(
+            ParseUserError::InvalidDigit {
+                literal: s[..end].to_owned(),
+                radix,
+                span: Span::new(0, end),
+            }
+            .into(),
+        ));
+    }
+
+    let value = BigInt::parse_bytes(digits.replace('_', "").as_bytes(), radix).unwrap();
+    Ok((rest, value.to_string()))
+}
+/// Parses number, which is converted to decimal notation with `.` and without digit separators.
+fn parse_number(s: &str) -> IResult<Literal> {
+    match parse_radix_integer(s) {
+        Ok((rest, integer)) => return Ok((rest, Literal::Decimal(integer))),
+        Err(nom::Err::Failure(error)) => return Err(nom::Err::Failure(error)),
+        Err(_) => {}
+    }
+
+    let locale = Syntax::current().locale;
+    let (rest, literal) = recognize_decimal(s)?;
+    let literal = literal
+        .chars()
+        .filter(|c| *c != '_' && Some(*c) != locale.grouping)
+        .map(|c| if c == locale.decimal { '.' } else { c })
+        .collect();
+    let mut chars = rest.chars();
+
+    // Imaginary suffix, unless it's the beginning of an identifier
+    match (chars.next(), chars.next()) {
+        (Some('i'), next) if !next.map_or(false, |c| c.is_alphanumeric() || c == '_') => {
+            Ok((&rest[1..], Literal::Imaginary(literal)))
+        }
+        _ => Ok((rest, Literal::Decimal(literal))),
+    }
+}
+/// Unit symbol with optional integer power, e.g. `km` or `s^-2`.
+fn parse_unit_term(s: &str) -> IResult<Unit> {
+    let (rest, symbol) = parse_identifier(s)?;
+    let unit = match Unit::lookup(symbol) {
+        Some(unit) => unit,
+        None => return Err(nom::Err::Error(ParseError::Nom((s, ErrorKind::Tag)))),
+    };
+
+    let power: IResult<&str> = preceded(
+        alt((tag("^"), tag("**"))),
+        recognize(pair(opt(char('-')), digit1)),
+    )(rest);
+
+    match power {
+        Ok((rest, power)) => match power.parse() {
+            Ok(power) => Ok((rest, unit.powi(power))),
+            Err(_) => Err(nom::Err::Error(ParseError::Nom((s, ErrorKind::TooLarge)))),
+        },
+        Err(_) => Ok((rest, unit)),
+    }
+}
+/// Unit written without spaces, e.g. `kg*m/s^2`.
+fn parse_unit(s: &str) -> IResult<Unit> {
+    let (mut s, mut unit) = parse_unit_term(s)?;
+
+    while let Ok((rest, (operator, term))) = pair(one_of("*/"), parse_unit_term)(s) {
+        unit = match operator {
+            '*' => unit.mul(&term),
+            _ => unit.mul(&term.powi(-1)),
+        };
+        s = rest;
+    }
+
+    Ok((s, unit))
+}
+/// Conversion keyword `in` or `to` followed by unit.
+fn parse_conversion(s: &str) -> IResult<Unit> {
+    let (rest, keyword) = parse_identifier(s)?;
+    if keyword != "in" && keyword != "to" {
+        return Err(nom::Err::Error(ParseError::Nom((s, ErrorKind::Tag))));
+    }
+
+    preceded(skip_whitespace, parse_unit)(rest)
+}
+/// Keyword of conditional expression, unless it's the beginning of a longer identifier.
+fn parse_keyword(s: &str) -> IResult<Token> {
+    match parse_identifier(s)? {
+        (rest, "if") => Ok((rest, Token::If)),
+        (rest, "then") => Ok((rest, Token::Then)),
+        (rest, "else") => Ok((rest, Token::Else)),
+        _ => Err(nom::Err::Error(ParseError::Nom((s, ErrorKind::Tag)))),
+    }
+}
+fn parse_identifier(s: &str) -> IResult<&str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0(alt((alphanumeric1, tag("_")))),
+    ))(s)
+}
+fn skip_whitespace(s: &str) -> IResult<()> {
+    let comment = preceded(char('#'), take_while(|c| c != '\n'));
+
+    Ok((
+        many0(alt((recognize(one_of(" \t\x0c\r")), comment)))(s)?.0,
+        (),
+    ))
+}
+fn parse_token(s: &str) -> IResult<Token> {
+    alt((
+        map(parse_operator, Token::Operator),
+        map(parse_conversion, Token::Conversion),
+        parse_keyword,
+        map(parse_identifier, |identifier| {
+            Token::Identifier(identifier.to_owned())
+        }),
+        map(parse_number, Token::Number),
+        map(char('('), |_| Token::OpenParenthesis),
+        map(char(')'), |_| Token::CloseParenthesis),
+        map(char('['), |_| Token::OpenBracket),
+        map(char(']'), |_| Token::CloseBracket),
+        map(char('%'), |_| Token::Percent),
+        map(char('?'), |_| Token::Question),
+        map(char(':'), |_| Token::Colon),
+        map(char(','), |_| Token::Comma),
+        map(one_of(";\n"), |_| Token::Separator),
+        map(char('='), |_| Token::Assign),
+    ))(s)
+}
+
+/// Splits the whole string into tokens, remembering where each of them was found.
+/// Unexpected characters are reported and skipped, so all of them are found at once.
+/// Typographic operators like `×` or `²` are accepted, while spans point at them.
+pub fn parse_recovering(s: &str) -> (Vec<Spanned<Token>>, Vec<ParseError>) {
+    let normalized = Normalized::new(s);
+    let (mut tokens, mut errors) = tokenize(&normalized.text);
+
+    for token in &mut tokens {
+        token.span = normalized.original(token.span);
+    }
+    for error in &mut errors {
+        match error {
+            ParseError::User(ParseUserError::UnexpectedCharacter { span, .. })
+            | ParseError::User(ParseUserError::InvalidDigit { span, .. }) => {
+                *span = normalized.original(*span);
+            }
+            _ => {}
+        }
+    }
+
+    (tokens, errors)
+}
+
+/// Splits canonical text into tokens.
+fn tokenize(s: &str) -> (Vec<Spanned<Token>>, Vec<ParseError>) {
+    let syntax = Syntax::current();
+    let argument = syntax.locale.argument;
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut input = s;
+    // Open parenthesis and brackets, inside which `;` may separate arguments
+    let mut depth = 0usize;
+
+    loop {
+        input = skip_whitespace(input).map_or(input, |(rest, ())| rest);
+
+        let start = s.len() - input.len();
+        let character = match input.chars().next() {
+            Some(character) => character,
+            None => break,
+        };
+
+        match parse_token(input) {
+            Ok((rest, token)) => {
+                let token = match token {
+                    Token::OpenParenthesis | Token::OpenBracket => {
+                        depth += 1;
+                        token
+                    }
+                    Token::CloseParenthesis | Token::CloseBracket => {
+                        depth = depth.saturating_sub(1);
+                        token
+                    }
+                    Token::Separator if depth > 0 && character == argument => Token::Comma,
+                    // New line ends the statement even with unclosed parenthesis
+                    Token::Separator => {
+                        depth = 0;
+                        token
+                    }
+                    token => token,
+                };
+                let is_number = match token {
+                    Token::Number(_) => true,
+                    _ => false,
+                };
+                tokens.push(Spanned::new(token, Span::new(start, s.len() - rest.len())));
+                input = rest;
+
+                // Unit suffix is recognized only after numbers, so it doesn't shadow variables,
+                // and not before parenthesis, so `2 min(3, 4)` calls the function
+                if is_number && syntax.units {
+                    let suffix = preceded(skip_whitespace, parse_unit)(input)
+                        .ok()
+                        .filter(|(rest, _)| !rest.trim_start().starts_with('('));
+                    if let Some((rest, unit)) = suffix {
+                        let end = s.len() - rest.len();
+                        let start = end - input[..input.len() - rest.len()].trim_start().len();
+                        tokens.push(Spanned::new(Token::Unit(unit), Span::new(start, end)));
+                        input = rest;
+                    }
+                }
+            }
+            // Invalid number is skipped as a whole
+            Err(nom::Err::Failure(ParseError::User(ParseUserError::InvalidDigit {
+                literal,
+                radix,
+                span,
+            }))) => {
+                errors.push(
+                    ParseUserError::InvalidDigit {
+                        literal,
+                        radix,
+                        span: Span::new(start + span.start, start + span.end),
+                    }
+                    .into(),
+                );
+                input = &input[span.end..];
+            }
+            Err(_) => {
+                errors.push(
+                    ParseUserError::UnexpectedCharacter {
+                        character,
+                        span: Span::new(start, start + character.len_utf8()),
+                    }
+                    .into(),
+                );
+                input = &input[character.len_utf8()..];
+            }
+        }
+    }
+
+    if tokens.is_empty() && errors.is_empty() {
+        errors.push(ParseUserError::EmptyInput.into());
+    }
+
+    (tokens, errors)
+}
+
+/// Splits the whole string into tokens, failing on the first unexpected character.
+pub fn parse(s: &str) -> IResult<Vec<Spanned<Token>>, ParseError> {
+    let (tokens, mut errors) = parse_recovering(s);
+
+    if errors.is_empty() {
+        Ok((&s[s.len()..], tokens))
+    } else {
+        Err(nom::Err::Failure(errors.remove(0)))
+    }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::syntax::Locale;
     #[test]
     fn test_skip_whitespace() {
         assert_eq!("bla b ", skip_whitespace("   bla b ").unwrap().0);
         assert_eq!("bla", skip_whitespace("bla").unwrap().0);
+        assert_eq!("\nbla", skip_whitespace(" # comment\nbla").unwrap().0);
     }
     #[test]
     fn test_operator() {
@@ -152,6 +622,200 @@ mod tests {
         assert_eq!(Operator::Minus, parse_operator("-").unwrap().1);
         assert_eq!(Operator::Multiply, parse_operator("*").unwrap().1);
         assert_eq!(Operator::Divide, parse_operator("/").unwrap().1);
+        assert_eq!(Operator::Power, parse_operator("^").unwrap().1);
+        assert_eq!(Operator::Power, parse_operator("**2").unwrap().1);
+        assert_eq!(Operator::Multiply, parse_operator("*2").unwrap().1);
+        assert_eq!(Operator::PlusMinus, parse_operator("±0.1").unwrap().1);
+        assert_eq!(Operator::FloorDivide, parse_operator("//2").unwrap().1);
+        assert_eq!(Operator::Remainder, parse_operator("mod 2").unwrap().1);
+        assert_eq!(Operator::Factorial, parse_operator("!").unwrap().1);
+        assert!(parse_operator("%").is_err());
+        assert_eq!(Operator::NotEqual, parse_operator("!=").unwrap().1);
+        assert_eq!(Operator::LessEqual, parse_operator("<=").unwrap().1);
+        assert_eq!(Operator::ShiftLeft, parse_operator("<<").unwrap().1);
+        assert_eq!(Operator::Not, parse_operator("not x").unwrap().1);
+        assert!(parse_operator("order").is_err());
+        assert_eq!(Operator::ShiftLeft, parse_operator("<<2").unwrap().1);
+        assert_eq!(Operator::BitXor, parse_operator("xor 2").unwrap().1);
+        assert!(parse_operator("xor_mask").is_err());
+        assert_eq!(Operator::Of, parse_operator("of 80").unwrap().1);
         assert!(parse_operator("b").is_err());
     }
+    #[test]
+    fn test_identifier() {
+        assert_eq!(("(2)", "sqrt"), parse_identifier("sqrt(2)").unwrap());
+        assert_eq!(("", "log_10"), parse_identifier("log_10").unwrap());
+        assert!(parse_identifier("10log").is_err());
+    }
+    #[test]
+    fn test_number() {
+        assert_eq!(
+            ("", Literal::Imaginary("2.5".to_owned())),
+            parse_number("2.5i").unwrap()
+        );
+        assert_eq!(
+            ("in", Literal::Decimal("2".to_owned())),
+            parse_number("2in").unwrap()
+        );
+        assert_eq!(
+            ("", Literal::Decimal("31".to_owned())),
+            parse_number("0x1_F").unwrap()
+        );
+        assert_eq!(
+            ("", Literal::Decimal("10".to_owned())),
+            parse_number("0b1010").unwrap()
+        );
+        for source in &["0o19", "0b102", "0x1G", "0x"] {
+            assert!(parse_number(source).is_err());
+        }
+        let (tokens, errors) = parse_recovering("0o19 + 0x1G");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::User(ParseUserError::InvalidDigit {
+                    literal: "0o19".to_owned(),
+                    radix: 8,
+                    span: Span::new(0, 4),
+                }),
+                ParseError::User(ParseUserError::InvalidDigit {
+                    literal: "0x1G".to_owned(),
+                    radix: 16,
+                    span: Span::new(7, 11),
+                }),
+            ]
+        );
+        assert_eq!(
+            ("", Literal::Decimal("1000000.5e-3".to_owned())),
+            parse_number("1_000_000.5e-3").unwrap()
+        );
+        assert_eq!(
+            ("e", Literal::Decimal("2".to_owned())),
+            parse_number("2e").unwrap()
+        );
+    }
+    #[test]
+    fn test_locale() {
+        let syntax = |locale| Syntax {
+            locale,
+            ..Syntax::default()
+        };
+        let decimal = |s: &str| Literal::Decimal(s.to_owned());
+
+        syntax(Locale::GERMAN).scope(|| {
+            assert_eq!(("", decimal("1234.56")), parse_number("1.234,56").unwrap());
+            assert_eq!((".5", decimal("1")), parse_number("1.5").unwrap());
+            assert_eq!((".50", decimal("1000")), parse_number("1.000.50").unwrap());
+
+            let (tokens, errors) = parse_recovering("max(1,5; 2); 3");
+            assert!(errors.is_empty());
+            let tokens: Vec<_> = tokens
+                .iter()
+                .map(|token| format!("{:?}", token.node))
+                .collect();
+            assert_eq!(tokens[3], "Comma");
+            assert_eq!(tokens[6], "Separator");
+        });
+        syntax(Locale::FRENCH).scope(|| {
+            assert_eq!(("", decimal("1234.5")), parse_number("1 234,5").unwrap());
+            assert_eq!((" 34", decimal("12")), parse_number("12 34").unwrap());
+        });
+        syntax(Locale::SWISS).scope(|| {
+            assert_eq!(
+                ("", decimal("1000000.5")),
+                parse_number("1'000'000.5").unwrap()
+            );
+        });
+    }
+    #[test]
+    fn test_unit() {
+        let (rest, unit) = parse_unit("kg*m/s^2 + 1").unwrap();
+        assert_eq!(rest, " + 1");
+        assert_eq!(unit.to_string(), "kg*m/s^2");
+        assert_eq!(
+            parse_conversion("in km").unwrap().1,
+            Unit::lookup("km").unwrap()
+        );
+        assert!(parse_conversion("inch").is_err());
+
+        let units = Syntax {
+            units: true,
+            ..Syntax::default()
+        };
+        let (tokens, _) = units.scope(|| parse_recovering("3 km in mi"));
+        assert_eq!(tokens.len(), 3);
+        assert_eq!((tokens[1].span.start, tokens[1].span.end), (2, 4));
+
+        // Units are taken only in units mode and not for function calls
+        assert_eq!(parse_recovering("2t").0.len(), 2);
+        let (tokens, _) = units.scope(|| parse_recovering("2 min(3, 4)"));
+        match tokens[1].node {
+            Token::Identifier(ref name) => assert_eq!(name, "min"),
+            ref token => panic!("Unexpected token {:?}", token),
+        }
+    }
+    #[test]
+    fn test_spans() {
+        let (_, tokens) = parse(" sqrt(x) ** 2").unwrap();
+        let spans: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(1, 5), (5, 6), (6, 7), (7, 8), (9, 11), (12, 13)]
+        );
+
+        assert_eq!(
+            parse("1 + $").unwrap_err(),
+            nom::Err::Failure(ParseError::User(ParseUserError::UnexpectedCharacter {
+                character: '$',
+                span: Span::new(4, 5)
+            }))
+        );
+        assert_eq!(
+            parse("  ").unwrap_err(),
+            nom::Err::Failure(ParseError::User(ParseUserError::EmptyInput))
+        );
+    }
+    #[test]
+    fn test_typographic() {
+        let spans = |s| {
+            parse(s)
+                .unwrap()
+                .1
+                .iter()
+                .map(|token| (token.span.start, token.span.end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans("2×x²"), vec![(0, 1), (1, 3), (3, 4), (4, 6), (4, 6)]);
+        assert_eq!(spans("√2"), vec![(0, 3), (0, 3), (3, 4), (4, 4)]);
+
+        let (tokens, errors) = parse_recovering("π − 1 ÷ $");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(
+            errors,
+            vec![ParseError::User(ParseUserError::UnexpectedCharacter {
+                character: '$',
+                span: Span::new(12, 13)
+            })]
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let (tokens, errors) = parse_recovering("1 $ 2 @@ 3");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| match error {
+                    ParseError::User(ParseUserError::UnexpectedCharacter { span, .. }) =>
+                        span.start,
+                    error => panic!("Unexpected error {:?}", error),
+                })
+                .collect::<Vec<_>>(),
+            vec![2, 6, 7]
+        );
+    }
 }
@@ -0,0 +1,181 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Typographic forms of operators, e.g. `×` or `²`, which come in text copied from documents.
+
+use crate::span::Span;
+
+/// Source with typographic operators replaced by canonical ones, which remembers
+/// where every byte came from, so spans still point at what user typed.
+#[derive(Clone, Debug)]
+pub struct Normalized {
+    pub text: String,
+    /// Span in the source of every byte of the text.
+    origins: Vec<Span>,
+    source_len: usize,
+}
+
+fn replacement(c: char) -> Option<&'static str> {
+    match c {
+        '×' | '·' | '⋅' | '∙' => Some("*"),
+        '÷' | '∕' => Some("/"),
+        '−' | '–' => Some("-"),
+        '≠' => Some("!="),
+        '≤' => Some("<="),
+        '≥' => Some(">="),
+        // Surrounded by spaces, so `πr` doesn't become identifier `pir`
+        'π' => Some(" pi "),
+        _ => None,
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => std::char::from_digit(c as u32 - '⁰' as u32, 10),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
+/// Length of number, identifier or another root at the beginning of the string,
+/// which is the operand of `√`.
+fn operand_len(s: &str) -> usize {
+    let mut chars = s.chars();
+    let is_part: fn(char) -> bool = match chars.next() {
+        Some(c) if c.is_ascii_digit() || c == '.' => |c| c.is_ascii_digit() || c == '.' || c == '_',
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => |c| c.is_ascii_alphanumeric() || c == '_',
+        Some('π') => return 'π'.len_utf8(),
+        Some('√') => return '√'.len_utf8() + operand_len(chars.as_str()),
+        _ => return 0,
+    };
+
+    s.find(|c| !is_part(c)).unwrap_or(s.len())
+}
+
+impl Normalized {
+    pub fn new(source: &str) -> Normalized {
+        let mut normalized = Normalized {
+            text: String::with_capacity(source.len()),
+            origins: Vec::with_capacity(source.len()),
+            source_len: source.len(),
+        };
+        // Ends of operands of `√`, where closing parenthesis are inserted, the innermost last
+        let mut close_at = Vec::new();
+
+        for (start, c) in source.char_indices() {
+            while close_at.last() == Some(&start) {
+                normalized.push(")", Span::at(start));
+                close_at.pop();
+            }
+
+            let span = Span::new(start, start + c.len_utf8());
+            let previous = source[..start].chars().next_back();
+
+            if let Some(text) = replacement(c) {
+                normalized.push(text, span);
+            } else if let Some(digit) = superscript(c) {
+                // Power is written once for the whole exponent, e.g. `10⁻³` is `10^-3`
+                if previous.and_then(superscript).is_none() {
+                    normalized.push("^", span);
+                }
+                normalized.push(digit.encode_utf8(&mut [0; 4]), span);
+            } else if c == '√' {
+                // `√2` is `sqrt(2)`, while `√(x + 1)` already has the parenthesis
+                match operand_len(&source[span.end..]) {
+                    0 => normalized.push("sqrt", span),
+                    len => {
+                        normalized.push("sqrt(", span);
+                        close_at.push(span.end + len);
+                    }
+                }
+            } else {
+                normalized.push(c.encode_utf8(&mut [0; 4]), span);
+            }
+        }
+
+        for _ in close_at {
+            normalized.push(")", Span::at(source.len()));
+        }
+
+        normalized
+    }
+
+    fn push(&mut self, text: &str, origin: Span) {
+        self.text.push_str(text);
+        self.origins
+            .extend(std::iter::repeat(origin).take(text.len()));
+    }
+
+    /// Span in the source, which the span in the normalized text came from.
+    pub fn original(&self, span: Span) -> Span {
+        let origin = |position: usize| {
+            self.origins
+                .get(position)
+                .copied()
+                .unwrap_or_else(|| Span::at(self.source_len))
+        };
+
+        if span.is_empty() {
+            Span::at(origin(span.start).start)
+        } else {
+            Span::new(origin(span.start).start, origin(span.end - 1).end)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalize = |s| Normalized::new(s).text;
+        assert_eq!(normalize("2×3 ÷ 4 − 1"), "2*3 / 4 - 1");
+        assert_eq!(normalize("x² + 10⁻¹²"), "x^2 + 10^-12");
+        assert_eq!(
+            normalize("√2 + √(x) + √π"),
+            "sqrt(2) + sqrt(x) + sqrt( pi )"
+        );
+        assert_eq!(normalize("2πr ≤ 1"), "2 pi r <= 1");
+        assert_eq!(normalize("√√16x"), "sqrt(sqrt(16))x");
+    }
+
+    #[test]
+    fn test_original() {
+        let normalized = Normalized::new("√2 × x²");
+        let original = |start, end| normalized.original(Span::new(start, end));
+        // `sqrt`
+        assert_eq!(original(0, 4), Span::new(0, 3));
+        // `sqrt(2)`
+        assert_eq!(original(0, 7), Span::new(0, 4));
+        // `*`
+        assert_eq!(original(8, 9), Span::new(5, 7));
+        // `^2`
+        assert_eq!(original(11, 13), Span::new(9, 11));
+        assert_eq!(original(13, 13), Span::at(11));
+    }
+}
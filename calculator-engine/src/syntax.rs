@@ -0,0 +1,299 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Settings of the accepted syntax, which are chosen by user.
+
+use crate::parser::Operator;
+use std::cell::Cell;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// Binding power of multiplication without operator, e.g. `2x` or `3(4 + 5)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImplicitMultiplication {
+    /// Same as `*`, so `1/2x` is `(1/2) * x`.
+    Same,
+    /// Tighter than `*` and `/`, but looser than `^`, so `1/2x` is `1 / (2 * x)`.
+    Tight,
+}
+
+impl ImplicitMultiplication {
+    /// Binding power of the product, like in `Operator::precedence`.
+    pub fn precedence(self) -> usize {
+        match self {
+            ImplicitMultiplication::Same => Operator::Multiply.precedence(),
+            ImplicitMultiplication::Tight => Operator::Multiply.precedence() + 1,
+        }
+    }
+}
+
+impl FromStr for ImplicitMultiplication {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "same" => Ok(ImplicitMultiplication::Same),
+            "tight" => Ok(ImplicitMultiplication::Tight),
+            _ => Err(format!(
+                "Unknown implicit multiplication {}, expected same or tight",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ImplicitMultiplication {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            ImplicitMultiplication::Same => write!(f, "same"),
+            ImplicitMultiplication::Tight => write!(f, "tight"),
+        }
+    }
+}
+
+/// Separators of numbers and arguments, e.g. `1.234,5` and `max(1; 2)` in German.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal: char,
+    /// Separator of thousands, which is accepted in groups of three digits and inserted into results.
+    pub grouping: Option<char>,
+    /// Separator of function arguments and interval bounds, `;` when comma is decimal.
+    pub argument: char,
+}
+
+impl Locale {
+    /// Plain notation, which is also used in English.
+    pub const C: Locale = Locale {
+        decimal: '.',
+        grouping: None,
+        argument: ',',
+    };
+    pub const GERMAN: Locale = Locale {
+        decimal: ',',
+        grouping: Some('.'),
+        argument: ';',
+    };
+    pub const FRENCH: Locale = Locale {
+        decimal: ',',
+        grouping: Some(' '),
+        argument: ';',
+    };
+    pub const SWISS: Locale = Locale {
+        decimal: '.',
+        grouping: Some('\''),
+        argument: ',',
+    };
+
+    /// Writes numbers of result like `-1234.5e-3` or `[1.5, 2]` in this locale.
+    pub fn format(self, result: &str) -> String {
+        let mut formatted = String::with_capacity(result.len());
+        let mut rest = result;
+
+        while let Some(c) = rest.chars().next() {
+            // Digits of identifiers and unit powers like `m^2` are left alone
+            let in_word = formatted.chars().next_back().map_or(false, |previous| {
+                previous.is_alphanumeric() || previous == '^'
+            });
+
+            if c.is_ascii_digit() && !in_word {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let (integer, after) = rest.split_at(end);
+
+                for (i, digit) in integer.chars().enumerate() {
+                    if let Some(grouping) = self.grouping {
+                        if i > 0 && (integer.len() - i) % 3 == 0 {
+                            formatted.push(grouping);
+                        }
+                    }
+                    formatted.push(digit);
+                }
+
+                rest = after;
+                if rest.starts_with('.') {
+                    let end = rest[1..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(rest.len(), |end| end + 1);
+                    formatted.push(self.decimal);
+                    formatted.push_str(&rest[1..end]);
+                    rest = &rest[end..];
+                }
+
+                // Exponent with optional sign is copied as is, e.g. `e-1234`
+                if rest.starts_with(&['e', 'E'][..]) {
+                    let sign = rest[1..].starts_with(&['-', '+'][..]) as usize;
+                    let end = rest[1 + sign..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(rest.len(), |end| end + 1 + sign);
+                    formatted.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                }
+            } else {
+                formatted.push(if c == ',' { self.argument } else { c });
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        formatted
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::C
+    }
+}
+
+/// Accepts names like `de` as well as values of `LANG` like `de_CH.UTF-8`.
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+            .replace('-', "_");
+        let language = name.split('_').next().unwrap_or_default();
+
+        match (name.as_str(), language) {
+            ("de_ch", _) | ("ch", _) => Ok(Locale::SWISS),
+            (_, "c") | (_, "posix") | (_, "en") => Ok(Locale::C),
+            (_, "de") => Ok(Locale::GERMAN),
+            (_, "fr") => Ok(Locale::FRENCH),
+            _ => Err(format!(
+                "Unknown locale {}, expected c, en, de, fr or ch",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Syntax {
+    pub implicit_multiplication: ImplicitMultiplication,
+    pub locale: Locale,
+    /// Whether numbers may be followed by unit, e.g. `3 km`, which is enabled in units mode.
+    /// Otherwise `2t` is multiplication by variable `t`.
+    pub units: bool,
+    /// Whether `i` is the imaginary unit, which is enabled in complex mode.
+    /// Otherwise it's an ordinary variable.
+    pub imaginary: bool,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax {
+            implicit_multiplication: ImplicitMultiplication::Same,
+            locale: Locale::default(),
+            units: false,
+            imaginary: false,
+        }
+    }
+}
+
+thread_local! {
+    static SYNTAX: Cell<Syntax> = Cell::new(Syntax::default());
+}
+
+impl Syntax {
+    /// Syntax of the source being parsed.
+    pub fn current() -> Syntax {
+        SYNTAX.with(Cell::get)
+    }
+
+    /// Parses sources in `f` with this syntax, restoring the previous one afterwards.
+    pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Syntax);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                SYNTAX.with(|syntax| syntax.set(self.0));
+            }
+        }
+
+        let _restore = Restore(SYNTAX.with(|syntax| syntax.replace(self)));
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::AstBuilder;
+
+    #[test]
+    fn test_format() {
+        let result = "-1234567.25, 1e-7, 1e1000, 100 m^2, 1000/3";
+        assert_eq!(Locale::C.format(result), result);
+        assert_eq!(
+            Locale::GERMAN.format(result),
+            "-1.234.567,25; 1e-7; 1e1000; 100 m^2; 1.000/3"
+        );
+        assert_eq!(Locale::SWISS.format("1234.5"), "1'234.5");
+        assert_eq!(
+            Locale::GERMAN.format("1.2e-1234, 5E+1000"),
+            "1,2e-1234; 5E+1000"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "-1234567.25 + 0.5",
+            "max(1234, 2.5 * 1e-3)",
+            "[1.5, 2000] + 0.125",
+            "1000000 / 7",
+            "1.2e-1234 * 2",
+        ];
+
+        for locale in &[Locale::C, Locale::GERMAN, Locale::FRENCH, Locale::SWISS] {
+            for source in &sources {
+                let syntax = Syntax {
+                    locale: *locale,
+                    ..Syntax::default()
+                };
+                let localized = locale.format(source);
+                let ast = syntax.scope(|| AstBuilder::build_ast(&localized)).unwrap();
+                assert_eq!(
+                    format!("{}", ast),
+                    format!("{}", AstBuilder::build_ast(source).unwrap()),
+                    "{} in {:?}",
+                    localized,
+                    locale
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_locale_name() {
+        assert_eq!("de_CH.UTF-8".parse(), Ok(Locale::SWISS));
+        assert_eq!("fr-FR".parse(), Ok(Locale::FRENCH));
+        assert_eq!("C".parse(), Ok(Locale::C));
+        assert!("xx".parse::<Locale>().is_err());
+    }
+}
@@ -0,0 +1,375 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+//! Units of measurement, which may follow numbers, e.g. `3 km` or `9.8 m/s^2`.
+
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Exponents of SI base dimensions, which are length, mass, time, current, temperature,
+/// amount of substance and luminous intensity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Dimension([i32; 7]);
+
+const DIMENSIONLESS: Dimension = Dimension([0, 0, 0, 0, 0, 0, 0]);
+const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+const AREA: Dimension = Dimension([2, 0, 0, 0, 0, 0, 0]);
+const VOLUME: Dimension = Dimension([3, 0, 0, 0, 0, 0, 0]);
+const SPEED: Dimension = Dimension([1, 0, -1, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension([-1, 1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension([2, 1, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension([2, 1, -3, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension([2, 1, -3, -1, 0, 0, 0]);
+const RESISTANCE: Dimension = Dimension([2, 1, -3, -2, 0, 0, 0]);
+
+/// Symbols of base dimensions in SI units.
+const BASE_SYMBOLS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+impl Dimension {
+    pub fn is_dimensionless(self) -> bool {
+        self == DIMENSIONLESS
+    }
+
+    fn mul(self, other: Dimension) -> Dimension {
+        let mut result = self;
+        for (exponent, other) in result.0.iter_mut().zip(other.0.iter()) {
+            *exponent += other;
+        }
+        result
+    }
+
+    fn powi(self, power: i32) -> Dimension {
+        let mut result = self;
+        for exponent in result.0.iter_mut() {
+            *exponent *= power;
+        }
+        result
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let terms = BASE_SYMBOLS
+            .iter()
+            .zip(self.0.iter())
+            .filter(|(_, exponent)| **exponent != 0)
+            .map(|(symbol, exponent)| UnitTerm {
+                symbol: (*symbol).to_owned(),
+                factor: 1.0,
+                dimension: DIMENSIONLESS,
+                exponent: *exponent,
+            })
+            .collect();
+
+        write!(f, "{}", Unit { terms })
+    }
+}
+
+/// Unit, which may be written in expressions.
+struct Definition {
+    symbol: &'static str,
+    /// Size of the unit in SI base units.
+    factor: f64,
+    dimension: Dimension,
+    /// Whether SI prefixes like `k` in `km` are allowed.
+    prefixable: bool,
+}
+
+macro_rules! units {
+    ($(($symbol:literal, $factor:expr, $dimension:ident, $prefixable:literal),)+) => {
+        &[$(Definition {
+            symbol: $symbol,
+            factor: $factor,
+            dimension: $dimension,
+            prefixable: $prefixable,
+        },)+]
+    };
+}
+
+const UNITS: &[Definition] = units![
+    // Kilogram is the base unit, but prefixes are applied to gram
+    ("m", 1.0, LENGTH, true),
+    ("g", 1e-3, MASS, true),
+    ("s", 1.0, TIME, true),
+    ("A", 1.0, CURRENT, true),
+    ("K", 1.0, TEMPERATURE, true),
+    ("mol", 1.0, AMOUNT, true),
+    ("cd", 1.0, LUMINOSITY, true),
+    ("Hz", 1.0, FREQUENCY, true),
+    ("N", 1.0, FORCE, true),
+    ("Pa", 1.0, PRESSURE, true),
+    ("J", 1.0, ENERGY, true),
+    ("W", 1.0, POWER, true),
+    ("C", 1.0, CHARGE, true),
+    ("V", 1.0, VOLTAGE, true),
+    ("ohm", 1.0, RESISTANCE, true),
+    ("L", 1e-3, VOLUME, true),
+    ("eV", 1.602_176_634e-19, ENERGY, true),
+    ("Wh", 3600.0, ENERGY, true),
+    ("cal", 4.184, ENERGY, true),
+    ("bar", 1e5, PRESSURE, true),
+    ("min", 60.0, TIME, false),
+    ("h", 3600.0, TIME, false),
+    ("day", 86400.0, TIME, false),
+    ("week", 604_800.0, TIME, false),
+    ("year", 31_557_600.0, TIME, false),
+    // `in` is the conversion keyword, so inch is written in full
+    ("inch", 0.0254, LENGTH, false),
+    ("ft", 0.3048, LENGTH, false),
+    ("yd", 0.9144, LENGTH, false),
+    ("mi", 1609.344, LENGTH, false),
+    ("nmi", 1852.0, LENGTH, false),
+    ("mph", 1609.344 / 3600.0, SPEED, false),
+    ("kph", 1000.0 / 3600.0, SPEED, false),
+    ("knot", 1852.0 / 3600.0, SPEED, false),
+    ("t", 1000.0, MASS, false),
+    ("lb", 0.453_592_37, MASS, false),
+    ("oz", 0.028_349_523_125, MASS, false),
+    ("ha", 1e4, AREA, false),
+    ("acre", 4_046.856_422_4, AREA, false),
+    ("gal", 3.785_411_784e-3, VOLUME, false),
+    ("atm", 101_325.0, PRESSURE, false),
+    ("psi", 6_894.757_293_168, PRESSURE, false),
+    ("hp", 745.699_871_582_270_2, POWER, false),
+];
+
+/// SI prefixes, micro is written as `u`.
+const PREFIXES: &[(&str, f64)] = &[
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("da", 1e1),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+];
+
+/// Unit symbol raised to integer power, e.g. `s^-2`.
+#[derive(Clone, Debug, PartialEq)]
+struct UnitTerm {
+    symbol: String,
+    factor: f64,
+    dimension: Dimension,
+    exponent: i32,
+}
+
+/// Product of unit symbols raised to powers, e.g. `kg*m/s^2`.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Unit {
+    terms: Vec<UnitTerm>,
+}
+
+impl Unit {
+    /// Finds unit by symbol, which may have SI prefix, e.g. `km`.
+    pub fn lookup(symbol: &str) -> Option<Unit> {
+        let definition = |symbol: &str| UNITS.iter().find(|unit| unit.symbol == symbol);
+
+        let (factor, dimension) = match definition(symbol) {
+            Some(unit) => (unit.factor, unit.dimension),
+            None => PREFIXES.iter().find_map(|(prefix, scale)| {
+                if !symbol.starts_with(prefix) {
+                    return None;
+                }
+                definition(&symbol[prefix.len()..])
+                    .filter(|unit| unit.prefixable)
+                    .map(|unit| (scale * unit.factor, unit.dimension))
+            })?,
+        };
+
+        Some(Unit {
+            terms: vec![UnitTerm {
+                symbol: symbol.to_owned(),
+                factor,
+                dimension,
+                exponent: 1,
+            }],
+        })
+    }
+
+    /// Whether unit doesn't have any symbols, like unit of plain numbers.
+    /// Symbol of unit, which consists of a single symbol without power, e.g. `km`.
+    pub fn symbol(&self) -> Option<&str> {
+        match self.terms.as_slice() {
+            [term] if term.exponent == 1 => Some(&term.symbol),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Size of the unit in SI base units.
+    pub fn factor(&self) -> f64 {
+        self.terms
+            .iter()
+            .map(|term| term.factor.powi(term.exponent))
+            .product()
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.terms.iter().fold(DIMENSIONLESS, |dimension, term| {
+            dimension.mul(term.dimension.powi(term.exponent))
+        })
+    }
+
+    /// Product of units, where exponents of the same symbols are added.
+    pub fn mul(&self, other: &Unit) -> Unit {
+        let mut terms = self.terms.clone();
+
+        for term in &other.terms {
+            match terms
+                .iter_mut()
+                .find(|existing| existing.symbol == term.symbol)
+            {
+                Some(existing) => existing.exponent += term.exponent,
+                None => terms.push(term.clone()),
+            }
+        }
+        terms.retain(|term| term.exponent != 0);
+
+        Unit { terms }
+    }
+
+    pub fn powi(&self, power: i32) -> Unit {
+        Unit {
+            terms: self
+                .terms
+                .iter()
+                .filter(|_| power != 0)
+                .map(|term| UnitTerm {
+                    exponent: term.exponent * power,
+                    ..term.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// Root of the given degree, when every exponent is divisible by it, e.g. `m^2` for `sqrt`.
+    pub fn root(&self, degree: i32) -> Option<Unit> {
+        self.terms
+            .iter()
+            .map(|term| {
+                if term.exponent % degree == 0 {
+                    Some(UnitTerm {
+                        exponent: term.exponent / degree,
+                        ..term.clone()
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<_>>()
+            .map(|terms| Unit { terms })
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let term = |symbol: &str, exponent: i32| {
+            if exponent == 1 {
+                symbol.to_owned()
+            } else {
+                format!("{}^{}", symbol, exponent)
+            }
+        };
+
+        let numerator = self
+            .terms
+            .iter()
+            .filter(|term| term.exponent > 0)
+            .map(|unit| term(&unit.symbol, unit.exponent))
+            .collect::<Vec<_>>();
+        let denominator = self
+            .terms
+            .iter()
+            .filter(|term| term.exponent < 0)
+            .map(|unit| format!("/{}", term(&unit.symbol, -unit.exponent)));
+
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        for term in denominator {
+            write!(f, "{}", term)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let kilometre = Unit::lookup("km").unwrap();
+        assert_eq!(kilometre.factor(), 1000.0);
+        assert_eq!(kilometre.dimension(), LENGTH);
+
+        assert_eq!(Unit::lookup("min").unwrap().factor(), 60.0);
+        assert_eq!(Unit::lookup("kg").unwrap().factor(), 1.0);
+        assert_eq!(Unit::lookup("kWh").unwrap().factor(), 3.6e6);
+        assert_eq!(Unit::lookup("kmin"), None);
+        assert_eq!(Unit::lookup("x"), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let metre = Unit::lookup("m").unwrap();
+        let second = Unit::lookup("s").unwrap();
+
+        let acceleration = metre.mul(&second.powi(-2));
+        assert_eq!(acceleration.to_string(), "m/s^2");
+        assert_eq!(acceleration.mul(&second).to_string(), "m/s");
+        assert_eq!(second.powi(-1).to_string(), "1/s");
+        assert!(metre.mul(&metre.powi(-1)).is_empty());
+        assert_eq!(metre.powi(2).root(2), Some(metre.clone()));
+        assert_eq!(metre.root(2), None);
+        assert_eq!(FORCE.to_string(), "m*kg/s^2");
+    }
+}
@@ -22,13 +22,17 @@
  */
 
 use calculator_engine::{
-    execution::hybrid::{Hybrid, JitOptimizationLevel},
+    execution::hybrid::{Environment, Hybrid, JitOptimizationLevel},
+    format::{Notation, MAX_DIGITS, MAX_EXACT_DIGITS},
+    number::{Complex, Integer, Number, Rational},
     parser::Operator,
+    syntax::{Locale, Syntax},
 };
 
 use gtk::{
-    ApplicationWindow, BuilderExtManual, Button, ButtonExt, Inhibit, TextBufferExt, TextView,
-    TextViewExt, WidgetExt,
+    ApplicationWindow, BuilderExtManual, Button, ButtonExt, ComboBoxExt, ComboBoxText, Grid,
+    Inhibit, SpinButton, SpinButtonExt, TextBufferExt, TextView, TextViewExt, ToggleButton,
+    ToggleButtonExt, WidgetExt,
 };
 use relm::{connect, Relm, Update, Widget};
 use relm_derive::Msg;
@@ -38,6 +42,14 @@ enum Msg {
     AddNumber(usize),
     AddOperator(Operator),
     AddText(&'static str),
+    /// Appends `%` directly to the number, so it's percentage like on a handheld calculator.
+    AddPercent,
+    /// Shows or hides programmer keypad, which switches to integer arithmetic.
+    ToggleProgrammer,
+    /// Notation or its number of digits was chosen in preferences.
+    ChangeNotation,
+    /// Switches between exact fractions and floating point numbers.
+    ToggleExact,
     DoCalculation,
     Quit,
 }
@@ -46,10 +58,28 @@ struct Widgets {
     window: ApplicationWindow,
     text_view_top: TextView,
     text_view_bottom: TextView,
+    button_programmer: ToggleButton,
+    programmer_keypad: Grid,
+    combo_notation: ComboBoxText,
+    spin_digits: SpinButton,
+    button_exact: ToggleButton,
 }
 
 struct Window {
     widgets: Widgets,
+    hybrid: Hybrid,
+    syntax: Syntax,
+    notation: Notation,
+}
+
+/// Locale of numbers from the environment, like other desktop applications do.
+fn environment_locale() -> Locale {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
 }
 
 impl Update for Window {
@@ -69,29 +99,112 @@ impl Update for Window {
                 Operator::Minus => " - ",
                 Operator::Divide => " / ",
                 Operator::Multiply => " * ",
+                Operator::Power => " ^ ",
+                Operator::PlusMinus => " ± ",
+                Operator::FloorDivide => " // ",
+                Operator::Remainder => " % ",
+                Operator::Of => " of ",
+                Operator::BitAnd => " & ",
+                Operator::BitOr => " | ",
+                Operator::BitXor => " xor ",
+                Operator::BitNot => "~",
+                Operator::ShiftLeft => " << ",
+                Operator::ShiftRight => " >> ",
+                Operator::Factorial => "!",
+                Operator::Equal => " == ",
+                Operator::NotEqual => " != ",
+                Operator::Less => " < ",
+                Operator::LessEqual => " <= ",
+                Operator::Greater => " > ",
+                Operator::GreaterEqual => " >= ",
+                Operator::And => " and ",
+                Operator::Or => " or ",
+                Operator::Not => "not ",
             }),
             Msg::DoCalculation => {}
             Msg::AddText(text) => top_buffer.insert_at_cursor(text),
+            Msg::AddPercent => top_buffer.insert_at_cursor("% "),
+            Msg::ToggleProgrammer => {
+                if self.widgets.button_programmer.get_active() {
+                    self.widgets.programmer_keypad.show();
+                } else {
+                    self.widgets.programmer_keypad.hide();
+                }
+            }
+            Msg::ChangeNotation => {
+                let digits = self.widgets.spin_digits.get_value_as_int().max(0) as usize;
+                // Zero digits keep the shortest mantissa in notations with exponent
+                let mantissa = Some(digits).filter(|digits| *digits > 0);
+
+                let id = self.widgets.combo_notation.get_active_id();
+                self.notation = match id.as_ref().map(|id| id.as_str()) {
+                    Some("fixed") => Notation::Fixed(digits),
+                    Some("significant") => Notation::Significant(digits.max(1)),
+                    Some("scientific") => Notation::Scientific(mantissa),
+                    Some("engineering") => Notation::Engineering(mantissa),
+                    Some("si") => Notation::SiPrefix(mantissa),
+                    _ => Notation::Shortest,
+                };
+            }
+            Msg::ToggleExact => {
+                // Exact results have more digits than `f64`, which keeps the chosen digits valid
+                let max_digits = if self.widgets.button_exact.get_active() {
+                    MAX_EXACT_DIGITS
+                } else {
+                    MAX_DIGITS
+                };
+                self.widgets.spin_digits.set_range(0.0, max_digits as f64);
+            }
             Msg::Quit => gtk::main_quit(),
         }
 
+        let input = top_buffer
+            .get_text(
+                &top_buffer.get_start_iter(),
+                &top_buffer.get_end_iter(),
+                false,
+            )
+            .unwrap()
+            .to_string();
+
+        // Programmer keypad shows integers both in hexadecimal and decimal
+        let hybrid = &self.hybrid;
+        let locale = self.syntax.locale;
+        let notation = self.notation;
+        let result = if self.widgets.button_programmer.get_active() {
+            self.syntax
+                .scope(|| hybrid.exec(&input, &mut Environment::<Integer>::default()))
+                .map(|result| {
+                    result.map(|result| {
+                        format!(
+                            "{} = {}",
+                            result.to_radix(16),
+                            locale.format(&result.to_notation(notation))
+                        )
+                    })
+                })
+        } else if self.widgets.button_exact.get_active() {
+            self.syntax
+                .scope(|| hybrid.exec(&input, &mut Environment::<Rational>::default()))
+                .map(|result| result.map(|result| locale.format(&result.to_notation(notation))))
+        } else {
+            // Complex numbers have the imaginary unit on the keypad
+            let complex = Syntax {
+                imaginary: true,
+                ..self.syntax
+            };
+            complex
+                .scope(|| hybrid.exec(&input, &mut Environment::<Complex>::default()))
+                .map(|result| result.map(|result| locale.format(&result.to_notation(notation))))
+        };
+
         self.widgets
             .text_view_bottom
             .get_buffer()
             .unwrap()
-            .set_text(&match Hybrid::exec(
-                &top_buffer
-                    .get_text(
-                        &top_buffer.get_start_iter(),
-                        &top_buffer.get_end_iter(),
-                        false,
-                    )
-                    .unwrap()
-                    .to_string(),
-                JitOptimizationLevel::None,
-            ) {
-                Ok(result) => result.to_string(),
-                Err(_) => "".to_owned(),
+            .set_text(&match result {
+                Ok(Some(result)) => result,
+                Ok(None) | Err(_) => "".to_owned(),
             });
     }
 }
@@ -161,6 +274,96 @@ impl Widget for Window {
             Msg::AddText(")")
         );
 
+        connect!(
+            relm,
+            builder.get_object::<Button>("button_i").unwrap(),
+            connect_clicked(_),
+            Msg::AddText("i")
+        );
+
+        connect!(
+            relm,
+            builder.get_object::<Button>("button_percent").unwrap(),
+            connect_clicked(_),
+            Msg::AddPercent
+        );
+
+        connect!(
+            relm,
+            builder.get_object::<Button>("button_of").unwrap(),
+            connect_clicked(_),
+            Msg::AddOperator(Operator::Of)
+        );
+
+        for (name, operator) in [
+            ("and", Operator::BitAnd),
+            ("or", Operator::BitOr),
+            ("xor", Operator::BitXor),
+            ("not", Operator::BitNot),
+            ("shl", Operator::ShiftLeft),
+            ("shr", Operator::ShiftRight),
+        ]
+        .iter()
+        {
+            connect!(
+                relm,
+                builder
+                    .get_object::<Button>(&format!("button_{}", name))
+                    .unwrap(),
+                connect_clicked(_),
+                Msg::AddOperator(*operator)
+            );
+        }
+
+        for (name, text) in [
+            ("hex_a", "A"),
+            ("hex_b", "B"),
+            ("hex_c", "C"),
+            ("hex_d", "D"),
+            ("hex_e", "E"),
+            ("hex_f", "F"),
+            ("prefix_hex", "0x"),
+            ("prefix_bin", "0b"),
+        ]
+        .iter()
+        {
+            connect!(
+                relm,
+                builder
+                    .get_object::<Button>(&format!("button_{}", name))
+                    .unwrap(),
+                connect_clicked(_),
+                Msg::AddText(*text)
+            );
+        }
+
+        let button_programmer: ToggleButton = builder.get_object("button_programmer").unwrap();
+        connect!(
+            relm,
+            button_programmer,
+            connect_toggled(_),
+            Msg::ToggleProgrammer
+        );
+
+        let combo_notation: ComboBoxText = builder.get_object("combo_notation").unwrap();
+        connect!(
+            relm,
+            combo_notation,
+            connect_changed(_),
+            Msg::ChangeNotation
+        );
+
+        let spin_digits: SpinButton = builder.get_object("spin_digits").unwrap();
+        connect!(
+            relm,
+            spin_digits,
+            connect_value_changed(_),
+            Msg::ChangeNotation
+        );
+
+        let button_exact: ToggleButton = builder.get_object("button_exact").unwrap();
+        connect!(relm, button_exact, connect_toggled(_), Msg::ToggleExact);
+
         connect!(
             relm,
             window,
@@ -175,7 +378,18 @@ impl Widget for Window {
                 window,
                 text_view_top,
                 text_view_bottom: builder.get_object("text_view_bottom").unwrap(),
+                button_programmer,
+                programmer_keypad: builder.get_object("programmer_keypad").unwrap(),
+                combo_notation,
+                spin_digits,
+                button_exact,
+            },
+            hybrid: Hybrid::new(JitOptimizationLevel::None),
+            syntax: Syntax {
+                locale: environment_locale(),
+                ..Syntax::default()
             },
+            notation: Notation::Shortest,
         }
     }
 }
@@ -21,24 +21,32 @@
  *
  */
 
+mod session;
+
 use ansi_term::Color;
+use calculator_engine::diagnostics::{check, render, ColorMode};
 use calculator_engine::execution::hybrid::{Hybrid, JitOptimizationLevel};
 use linefeed::{Interface, ReadResult, Signal};
 use pretty_env_logger::init;
+use session::Session;
 use std::sync::Arc;
 use std::time::Duration;
 
-fn get_prompt(draw_red: bool) -> String {
+/// Prompt, which is drawn red with number of errors, when the buffer has any.
+fn get_prompt(errors: usize) -> String {
     let red_style = Color::Red.bold();
     let green_style = Color::Green;
 
-    let prompt_text = "> ";
+    let prompt_text = match errors {
+        0 => "> ".to_owned(),
+        errors => format!("[{}] > ", errors),
+    };
 
     format!(
         "\x01{red_suffix}{green_suffix}{prompt_prefix}\x02{prompt_text}\x01{text_prefix}\x02",
         prompt_text = prompt_text,
         prompt_prefix = red_style.prefix(),
-        text_prefix = if draw_red {
+        text_prefix = if errors > 0 {
             red_style.prefix()
         } else {
             green_style.prefix()
@@ -53,11 +61,18 @@ fn main() -> std::io::Result<()> {
 
     let interface = Arc::new(Interface::new("calculator-repl")?);
 
-    interface.set_prompt(&get_prompt(false))?;
+    interface.set_prompt(&get_prompt(0))?;
 
     interface.set_history_size(10000);
 
     let mut last_buffer = String::new();
+    let mut session = Session::new();
+    let hybrid = Hybrid::new(JitOptimizationLevel::None);
+    let color_mode = if std::env::var_os("NO_COLOR").is_some() {
+        ColorMode::Plain
+    } else {
+        ColorMode::Ansi
+    };
 
     loop {
         match interface.read_line_step(Some(Duration::from_millis(30)))? {
@@ -67,10 +82,20 @@ fn main() -> std::io::Result<()> {
                         continue;
                     }
 
-                    match Hybrid::exec(&line, JitOptimizationLevel::None) {
-                        Ok(result) => println!("{}", result),
-                        Err(e) => println!("{}", e),
-                    };
+                    let trimmed = line.trim_start();
+
+                    if trimmed.starts_with(':') {
+                        match session.command(&trimmed[1..]) {
+                            Ok(message) => println!("{}", message),
+                            Err(message) => println!("{}", Color::Red.bold().paint(message)),
+                        }
+                    } else {
+                        match session.exec(&hybrid, &line) {
+                            Ok(None) => {}
+                            Ok(Some(result)) => println!("{}", result),
+                            Err(e) => print!("{}", render(&line, &e, color_mode)),
+                        };
+                    }
 
                     interface.add_history(line);
                 }
@@ -89,9 +114,17 @@ fn main() -> std::io::Result<()> {
                 let buffer = interface.buffer();
 
                 if buffer != last_buffer {
-                    interface.set_prompt(&get_prompt(
-                        Hybrid::exec(&buffer, JitOptimizationLevel::None).is_err(),
-                    ))?;
+                    let errors = if buffer.trim().is_empty() || buffer.trim_start().starts_with(':')
+                    {
+                        0
+                    } else {
+                        match session.syntax().scope(|| check(&buffer)).len() {
+                            0 if !session.is_valid(&hybrid, &buffer) => 1,
+                            errors => errors,
+                        }
+                    };
+
+                    interface.set_prompt(&get_prompt(errors))?;
 
                     last_buffer = buffer;
                 }
@@ -0,0 +1,275 @@
+/*
+ * Calculator
+ * Copyright (c) 2019 Alik Aslanyan <cplusplus256@gmail.com>
+ *
+ *
+ *    This file is part of Calculator.
+ *
+ *    Calculator is free software; you can redistribute it and/or modify it
+ *    under the terms of the GNU General Public License as published by the
+ *    Free Software Foundation; either version 3 of the License, or (at
+ *    your option) any later version.
+ *
+ *    This program is distributed in the hope that it will be useful, but
+ *    WITHOUT ANY WARRANTY; without even the implied warranty of
+ *    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+ *    General Public License for more details.
+ *
+ *    You should have received a copy of the GNU General Public License
+ *    along with this program; if not, write to the Free Software Foundation,
+ *    Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+ *
+ */
+
+use calculator_engine::ast::AstBuilder;
+use calculator_engine::execution::hybrid::{Environment, Hybrid};
+use calculator_engine::format::{Notation, MAX_DIGITS, MAX_EXACT_DIGITS};
+use calculator_engine::number::{
+    Complex, Context, Decimal, Integer, Interval, Number, Quantity, Rational, MAX_PRECISION,
+};
+use calculator_engine::syntax::Syntax;
+use calculator_engine::Error;
+
+/// Number type, which expressions are evaluated over.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Float,
+    Exact,
+    Decimal,
+    Complex,
+    Interval,
+    Integer,
+    Units,
+}
+
+impl Mode {
+    /// Largest number of digits of results, which are `f64` unless they are exact or decimal.
+    fn max_digits(self) -> usize {
+        match self {
+            Mode::Exact | Mode::Decimal => MAX_EXACT_DIGITS,
+            _ => MAX_DIGITS,
+        }
+    }
+}
+
+/// Variables and functions of every mode together with output settings.
+/// Every mode has its own environment, so switching modes doesn't lose definitions.
+pub struct Session {
+    mode: Mode,
+    /// Notation of results, shortest prints floats in the shortest form and rationals as fractions.
+    notation: Notation,
+    /// Precision and rounding of decimal mode.
+    context: Context,
+    /// Radix of integer results in float and integer modes.
+    base: u32,
+    syntax: Syntax,
+    float: Environment,
+    exact: Environment<Rational>,
+    decimal: Environment<Decimal>,
+    complex: Environment<Complex>,
+    interval: Environment<Interval>,
+    integer: Environment<Integer>,
+    units: Environment<Quantity>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            mode: Mode::Float,
+            notation: Notation::Shortest,
+            context: Context::default(),
+            base: 10,
+            syntax: Syntax::default(),
+            float: Environment::new(),
+            exact: Environment::default(),
+            decimal: Environment::default(),
+            complex: Environment::default(),
+            interval: Environment::default(),
+            integer: Environment::default(),
+            units: Environment::default(),
+        }
+    }
+
+    /// Syntax, which lines are parsed with, unit suffixes and `i` depend on the mode.
+    pub fn syntax(&self) -> Syntax {
+        Syntax {
+            units: self.mode == Mode::Units,
+            imaginary: self.mode == Mode::Complex,
+            ..self.syntax
+        }
+    }
+
+    /// Executes statement, returning formatted result.
+    pub fn exec(&mut self, hybrid: &Hybrid, line: &str) -> Result<Option<String>, Error> {
+        let syntax = self.syntax();
+        let base = self.base;
+
+        // Results in other bases have letters as digits, so they aren't localized
+        syntax
+            .scope(|| self.exec_in_mode(hybrid, line))
+            .map(|result| {
+                result.map(|result| {
+                    if base == 10 {
+                        syntax.locale.format(&result)
+                    } else {
+                        result
+                    }
+                })
+            })
+    }
+
+    fn exec_in_mode(&mut self, hybrid: &Hybrid, line: &str) -> Result<Option<String>, Error> {
+        let notation = self.notation;
+        let base = self.base;
+
+        match self.mode {
+            Mode::Float => hybrid.exec(line, &mut self.float).map(|result| {
+                result.map(|result| match Integer::from_f64(result) {
+                    Ok(integer) if base != 10 => integer.to_radix(base),
+                    _ => result.to_notation(notation),
+                })
+            }),
+            Mode::Exact => hybrid
+                .exec(line, &mut self.exact)
+                .map(|result| result.map(|result| result.to_notation(notation))),
+            Mode::Decimal => {
+                let decimal = &mut self.decimal;
+                // Fixed notation is rounded like the context
+                self.context.scope(|| {
+                    hybrid
+                        .exec(line, decimal)
+                        .map(|result| result.map(|result| result.to_notation(notation)))
+                })
+            }
+            Mode::Complex => hybrid
+                .exec(line, &mut self.complex)
+                .map(|result| result.map(|result| result.to_notation(notation))),
+            Mode::Interval => hybrid
+                .exec(line, &mut self.interval)
+                .map(|result| result.map(|result| result.to_notation(notation))),
+            Mode::Integer => hybrid.exec(line, &mut self.integer).map(|result| {
+                result.map(|result| match base {
+                    10 => result.to_notation(notation),
+                    base => result.to_radix(base),
+                })
+            }),
+            Mode::Units => hybrid
+                .exec(line, &mut self.units)
+                .map(|result| result.map(|result| result.to_notation(notation))),
+        }
+    }
+
+    /// Checks whether statement executes without errors, leaving the session untouched.
+    pub fn is_valid(&self, hybrid: &Hybrid, line: &str) -> bool {
+        self.syntax().scope(|| self.is_valid_in_mode(hybrid, line))
+    }
+
+    fn is_valid_in_mode(&self, hybrid: &Hybrid, line: &str) -> bool {
+        match self.mode {
+            Mode::Float => hybrid.exec(line, &mut self.float.clone()).is_ok(),
+            Mode::Exact => hybrid.exec(line, &mut self.exact.clone()).is_ok(),
+            Mode::Decimal => self
+                .context
+                .scope(|| hybrid.exec(line, &mut self.decimal.clone()))
+                .is_ok(),
+            Mode::Complex => hybrid.exec(line, &mut self.complex.clone()).is_ok(),
+            Mode::Interval => hybrid.exec(line, &mut self.interval.clone()).is_ok(),
+            Mode::Integer => hybrid.exec(line, &mut self.integer.clone()).is_ok(),
+            Mode::Units => hybrid.exec(line, &mut self.units.clone()).is_ok(),
+        }
+    }
+
+    /// Applies command like `:mode exact`, which is given without the colon.
+    pub fn command(&mut self, command: &str) -> Result<String, String> {
+        // Source after `:explain` keeps its spaces
+        let explained = command.trim_start();
+        if explained.starts_with("explain") {
+            let source = &explained["explain".len()..];
+            return self
+                .syntax()
+                .scope(|| AstBuilder::build_program(source))
+                .map(|program| format!("{:#}", program))
+                .map_err(|error| error.to_string());
+        }
+
+        let mut words = command.split_whitespace();
+
+        match (words.next(), words.next(), words.next()) {
+            (Some("mode"), Some(mode), None) => {
+                let new_mode = match mode {
+                    "float" => Mode::Float,
+                    "exact" => Mode::Exact,
+                    "decimal" => Mode::Decimal,
+                    "complex" => Mode::Complex,
+                    "interval" => Mode::Interval,
+                    "integer" => Mode::Integer,
+                    "units" => Mode::Units,
+                    mode => {
+                        return Err(format!(
+                            "Unknown mode {}, expected float, exact, decimal, complex, interval, integer or units",
+                            mode
+                        ))
+                    }
+                };
+                self.notation
+                    .check_digits(new_mode.max_digits())
+                    .map_err(|error| format!("{}, change digits first", error))?;
+                self.mode = new_mode;
+                Ok(format!("Mode: {}", mode))
+            }
+            (Some("digits"), Some("off"), None) => {
+                self.notation = Notation::Shortest;
+                Ok("Digits: off".to_owned())
+            }
+            (Some("digits"), Some(digits), None) => match digits.parse() {
+                Ok(digits) => {
+                    self.notation = Notation::Fixed(digits).check_digits(self.mode.max_digits())?;
+                    Ok(format!("Digits: {}", digits))
+                }
+                Err(_) => Err(format!("Invalid number of digits {}", digits)),
+            },
+            (Some("notation"), Some(notation), None) => {
+                self.notation = notation
+                    .parse::<Notation>()?
+                    .check_digits(self.mode.max_digits())?;
+                Ok(format!("Notation: {}", self.notation))
+            }
+            (Some("precision"), Some(precision), None) => match precision.parse() {
+                Ok(precision) if precision > 0 && precision <= MAX_PRECISION => {
+                    self.context.precision = precision;
+                    Ok(format!("Precision: {}", precision))
+                }
+                _ => Err(format!(
+                    "Invalid precision {}, expected 1 to {} digits",
+                    precision, MAX_PRECISION
+                )),
+            },
+            (Some("base"), Some(base), None) => match base.parse() {
+                Ok(base) if [2, 8, 10, 16].contains(&base) => {
+                    self.base = base;
+                    Ok(format!("Base: {}", base))
+                }
+                _ => Err(format!("Invalid base {}, expected 2, 8, 10 or 16", base)),
+            },
+            (Some("rounding"), Some(rounding), None) => {
+                self.context.rounding = rounding.parse()?;
+                Ok(format!("Rounding: {}", rounding))
+            }
+            (Some("locale"), Some(locale), None) => {
+                self.syntax.locale = locale.parse()?;
+                Ok(format!("Locale: {}", locale))
+            }
+            (Some("implicit"), Some(implicit), None) => {
+                self.syntax.implicit_multiplication = implicit.parse()?;
+                Ok(format!("Implicit multiplication: {}", implicit))
+            }
+            _ => Err(format!(
+                "Unknown command :{}, expected :mode float|exact|decimal|complex|interval|integer|units, :digits N|off, \
+                 :notation shortest|fixed:N|significant:N|scientific[:N]|engineering[:N]|si[:N], \
+                 :base 2|8|10|16, :precision N, :rounding half-even|half-up|toward-zero, \
+                 :locale c|en|de|fr|ch, :implicit same|tight or :explain EXPRESSION",
+                command.trim()
+            )),
+        }
+    }
+}